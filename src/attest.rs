@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Signing script written when `.bakehouse` doesn't name one
+const DEFAULT_SCRIPT: &str = "sign-images.sh";
+
+/// How much build detail the provenance attestation records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProvenanceMode {
+    /// No provenance attestation
+    Off,
+    /// The build's inputs and outputs
+    Min,
+    /// Also the build steps and arguments, minus secrets
+    #[default]
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttestConfig {
+    /// Whether to attach an SBOM of each image
+    #[serde(default = "default_sbom")]
+    pub sbom: bool,
+
+    #[serde(default)]
+    pub provenance: ProvenanceMode,
+
+    /// Write a script that signs the pushed images with cosign. No script when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sign: Option<SignConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SignConfig {
+    /// Where to write the script, relative to the workspace root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+
+    /// cosign key reference, e.g. `awskms://...` or `env://COSIGN_KEY`. Images are signed
+    /// keylessly when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+fn default_sbom() -> bool {
+    true
+}
+
+impl AttestConfig {
+    /// `attest` entries for every target
+    pub fn entries(&self) -> Vec<String> {
+        let mut entries = Vec::new();
+        if self.sbom {
+            entries.push("type=sbom".to_string());
+        }
+        match self.provenance {
+            ProvenanceMode::Off => entries.push("type=provenance,disabled=true".to_string()),
+            ProvenanceMode::Min => entries.push("type=provenance,mode=min".to_string()),
+            ProvenanceMode::Max => entries.push("type=provenance,mode=max".to_string()),
+        }
+        entries
+    }
+}
+
+impl SignConfig {
+    pub fn script_path(&self) -> PathBuf {
+        self.script.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_SCRIPT))
+    }
+
+    /// A script signing every image a bake run pushed, by digest. It reads the file
+    /// `docker buildx bake --push --metadata-file` writes, which lists each built target's
+    /// image names and digest, so only images from that run are signed
+    pub fn script(&self, bake_file: &str) -> String {
+        let key = match &self.key {
+            Some(key) => format!(" --key '{}'", key.replace('\'', r"'\''")),
+            None => String::new(),
+        };
+
+        format!(
+            r#"#!/bin/sh
+# Generated by bakehouse. Signs the images pushed by
+#
+#   docker buildx bake -f {bake_file} --push --metadata-file bake-metadata.json
+#
+# Usage: {script} [metadata file]. With DRY_RUN=1 the image references are only listed.
+set -eu
+
+metadata="${{1:-bake-metadata.json}}"
+
+# Sign digests rather than tags, which can be moved
+jq -r 'to_entries[]
+  | select(.value["containerimage.digest"] and .value["image.name"])
+  | .value["containerimage.digest"] as $digest
+  | .value["image.name"] | split(",")[]
+  | "\(sub(":[^:/]*$"; ""))@\($digest)"' "$metadata" |
+sort -u |
+while read -r reference; do
+  echo "$reference"
+  if [ "${{DRY_RUN:-}}" != "1" ]; then
+    cosign sign --yes{key} "$reference"
+  fi
+done
+"#,
+            bake_file = bake_file,
+            script = self.script_path().display(),
+            key = key,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries() {
+        let config: AttestConfig = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(config.entries(), vec!["type=sbom", "type=provenance,mode=max"]);
+
+        let config: AttestConfig = serde_yaml::from_str("sbom: false\nprovenance: off").unwrap();
+        assert_eq!(config.entries(), vec!["type=provenance,disabled=true"]);
+    }
+
+    #[test]
+    fn test_script() {
+        let keyless = SignConfig::default();
+        let script = keyless.script("docker-bake.hcl");
+        assert!(script.contains("docker buildx bake -f docker-bake.hcl --push --metadata-file bake-metadata.json"));
+        assert!(script.contains("    cosign sign --yes \"$reference\"\n"));
+
+        let keyed = SignConfig {
+            script: Some(PathBuf::from("ci/sign.sh")),
+            key: Some("env://COSIGN_KEY".to_string()),
+        };
+        assert_eq!(keyed.script_path(), PathBuf::from("ci/sign.sh"));
+        assert!(keyed
+            .script("docker-bake.json")
+            .contains("cosign sign --yes --key 'env://COSIGN_KEY' \"$reference\""));
+    }
+}
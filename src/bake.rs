@@ -1,18 +1,135 @@
-use hcl::Value;
-use indexmap::IndexMap;
-use serde::Serialize;
-use std::collections::HashMap;
+use anyhow::{bail, Result};
+use hcl::format::{Format, Formatter};
+use hcl::{Block, Body, Expression, Object, ObjectKey, TemplateExpr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize)]
+/// The formats `docker buildx bake -f` reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Hcl,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Hcl => "hcl",
+            OutputFormat::Json => "json",
+        }
+    }
+}
+
+/// Tags when `.bakehouse` doesn't set any: `name:version`, prefixed with `REGISTRY/` when
+/// that is set, and with the version replaced by `TAG` when that is
+pub const DEFAULT_TAG: &str =
+    r#"${(REGISTRY == "") ? "" : "${REGISTRY}/"}${name}:${(TAG == "") ? "${version}" : TAG}"#;
+
+/// Variables declared in every bake file, as the default tag uses them
+pub const DEFAULT_VARIABLES: [&str; 2] = ["REGISTRY", "TAG"];
+
+/// Fill in a package's `${name}` and `${version}` in a tag or cache ref pattern, leaving the
+/// rest for bake
+pub fn render_pattern(pattern: &str, name: &str, version: &str) -> String {
+    pattern.replace("${name}", name).replace("${version}", version)
+}
+
+/// Replace `${env:VAR}` placeholders with the bake variable `VAR`, which bake reads from the
+/// environment. Returns the variables used so they can be declared
+pub fn env_placeholders(value: &str) -> Result<(String, Vec<String>)> {
+    let mut rendered = String::new();
+    let mut variables = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${env:") {
+        let Some(end) = rest[start..].find('}') else {
+            bail!("Unterminated placeholder in '{}'", value);
+        };
+        let variable = &rest[start + "${env:".len()..start + end];
+        let is_identifier = variable.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && variable.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            bail!("Invalid environment variable '{}' in '{}'", variable, value);
+        }
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(&format!("${{{}}}", variable));
+        variables.push(variable.to_string());
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+
+    Ok((rendered, variables))
+}
+
+/// A bake target. Dependencies are expressed through `target:` entries in `contexts`,
+/// as bake has no separate attribute for them
+#[derive(Debug, Default, Serialize)]
 pub struct Target {
-    pub context: String,
-    pub dockerfile: String,
+    /// Name template for the targets a matrix expands to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Values of each matrix variable; one target is built per combination
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub matrix: BTreeMap<String, Vec<String>>,
+    /// Base targets to take settings from, later ones overriding earlier ones
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inherits: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dockerfile: Option<String>,
+    /// Dockerfile stage to build, the last one when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// What to run instead of a build, e.g. `check` for the Dockerfile build checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call: Option<String>,
+    /// HCL templates, so they may interpolate bake variables
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tags: Vec<String>,
-    pub depends_on: Vec<String>,
+    /// Exporters, as templates. Bake loads or pushes the image when empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub output: Vec<String>,
+    #[serde(skip)]
     pub dockerfile_contents: Option<String>,
-    pub contexts: HashMap<String, String>,
+    /// Named contexts. `target:` values are templates, so matrix targets can name the
+    /// combination they depend on; paths are literal
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", serialize_with = "contexts_map")]
+    pub contexts: BTreeMap<String, String>,
+    /// Build arguments. Values are templates, so they may use variables
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+    /// Image labels. Values are templates, like args
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Manifest annotations, as `key=value` templates
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub platforms: Vec<String>,
+    #[serde(rename = "cache-from", skip_serializing_if = "Vec::is_empty")]
+    pub cache_from: Vec<String>,
+    #[serde(rename = "cache-to", skip_serializing_if = "Vec::is_empty")]
+    pub cache_to: Vec<String>,
+    /// Attestations to attach to the image, e.g. `type=sbom`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attest: Vec<String>,
+    /// Secrets, as `type=file` or `type=env` templates
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secret: Vec<String>,
+    /// SSH agents or keys, as templates
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ssh: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entitlements: Vec<String>,
+    /// Any other bake attributes, such as `platforms` or `labels`. Strings are templates
+    #[serde(flatten)]
+    pub attributes: BTreeMap<String, serde_json::Value>,
 }
 
 impl Target {
@@ -21,8 +138,7 @@ impl Target {
         workspace_root: &Path,
         dockerfile: String,
         tags: Vec<String>,
-        depends_on: Vec<String>,
-        contexts: HashMap<String, String>,
+        contexts: BTreeMap<String, String>,
     ) -> Self {
         // First get the package path relative to the workspace root
         let relative_path = package_path
@@ -32,57 +148,235 @@ impl Target {
             .into_owned();
 
         // Remove any leading "./" and ensure paths are relative to workspace root
-        let context = relative_path.trim_start_matches("./").to_string();
+        let context = match relative_path.trim_start_matches("./") {
+            "" => ".".to_string(),
+            context => context.to_string(),
+        };
 
         Self {
-            context,
-            dockerfile,
+            context: Some(context),
+            dockerfile: Some(dockerfile),
             tags,
-            depends_on,
-            dockerfile_contents: None,
             contexts,
+            ..Default::default()
         }
     }
 
-    fn to_hcl(&self) -> Value {
-        let mut map = IndexMap::new();
-        map.insert("context".to_string(), Value::String(self.context.clone()));
-        map.insert(
-            "dockerfile".to_string(),
-            Value::String(self.dockerfile.clone()),
-        );
-        map.insert(
-            "tags".to_string(),
-            Value::Array(self.tags.iter().map(|t| Value::String(t.clone())).collect()),
-        );
-        map.insert(
-            "depends_on".to_string(),
-            Value::Array(
-                self.depends_on
-                    .iter()
-                    .map(|d| Value::String(d.clone()))
-                    .collect(),
-            ),
-        );
+    /// A target that only exists to be inherited from, holding shared settings
+    pub fn base(attributes: BTreeMap<String, serde_json::Value>) -> Self {
+        Self {
+            attributes,
+            ..Default::default()
+        }
+    }
+
+    fn to_block(&self, name: &str) -> Block {
+        let mut block = Block::builder("target").add_label(name);
 
-        // Add contexts if present
+        if let Some(name) = &self.name {
+            block = block.add_attribute(("name", template(name)));
+        }
+        if !self.matrix.is_empty() {
+            let matrix: Object<ObjectKey, Expression> = self
+                .matrix
+                .iter()
+                .map(|(key, values)| (ObjectKey::from(hcl::Identifier::unchecked(key)), values.clone().into()))
+                .collect();
+            block = block.add_attribute(("matrix", matrix));
+        }
+        if !self.inherits.is_empty() {
+            block = block.add_attribute(("inherits", self.inherits.clone()));
+        }
+        if let Some(context) = &self.context {
+            block = block.add_attribute(("context", context.as_str()));
+        }
+        if let Some(dockerfile) = &self.dockerfile {
+            block = block.add_attribute(("dockerfile", dockerfile.as_str()));
+        }
+        if let Some(target) = &self.target {
+            block = block.add_attribute(("target", target.as_str()));
+        }
+        if let Some(call) = &self.call {
+            block = block.add_attribute(("call", call.as_str()));
+        }
+        if !self.tags.is_empty() {
+            block = block.add_attribute(("tags", templates(&self.tags)));
+        }
+        if !self.output.is_empty() {
+            block = block.add_attribute(("output", templates(&self.output)));
+        }
         if !self.contexts.is_empty() {
-            let contexts_map = self
+            let contexts: Object<ObjectKey, Expression> = self
                 .contexts
                 .iter()
-                .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                .map(|(key, value)| (ObjectKey::Expression(Expression::String(key.clone())), context_value(value)))
                 .collect();
-            map.insert("contexts".to_string(), Value::Object(contexts_map));
+            block = block.add_attribute(("contexts", contexts));
+        }
+        for (key, map) in [("args", &self.args), ("labels", &self.labels)] {
+            if !map.is_empty() {
+                let object: Object<ObjectKey, Expression> = map
+                    .iter()
+                    .map(|(key, value)| (ObjectKey::Expression(Expression::String(key.clone())), template(value)))
+                    .collect();
+                block = block.add_attribute((key, object));
+            }
+        }
+        if !self.annotations.is_empty() {
+            block = block.add_attribute(("annotations", templates(&self.annotations)));
+        }
+        if !self.platforms.is_empty() {
+            block = block.add_attribute(("platforms", self.platforms.clone()));
+        }
+        for (key, entries) in [
+            ("cache-from", &self.cache_from),
+            ("cache-to", &self.cache_to),
+            ("attest", &self.attest),
+            ("secret", &self.secret),
+            ("ssh", &self.ssh),
+        ] {
+            if !entries.is_empty() {
+                block = block.add_attribute((key, templates(entries)));
+            }
+        }
+        if let Some(network) = &self.network {
+            block = block.add_attribute(("network", network.as_str()));
+        }
+        if !self.entitlements.is_empty() {
+            block = block.add_attribute(("entitlements", self.entitlements.clone()));
         }
+        for (key, value) in &self.attributes {
+            block = block.add_attribute((key.as_str(), expression(value)));
+        }
+
+        block.build()
+    }
+}
+
+/// An HCL template string, written without escaping `${` so it can interpolate variables
+fn template(value: &str) -> Expression {
+    if value.contains("${") || value.contains("%{") {
+        Expression::from(TemplateExpr::QuotedString(escape_literals(value)))
+    } else {
+        Expression::String(value.to_string())
+    }
+}
+
+/// Escape quotes, backslashes and newlines outside `${…}` and `%{…}`, which hcl-rs writes raw
+fn escape_literals(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut escaped = String::with_capacity(value.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if (c == '$' || c == '%') && next == Some(c) && chars.get(i + 2) == Some(&'{') {
+            // `$${` and `%%{` are already escaped literals
+            escaped.extend(&chars[i..i + 3]);
+            i += 3;
+        } else if (c == '$' || c == '%') && next == Some('{') {
+            // Copy the interpolation as is, up to its matching brace
+            let (mut depth, mut in_string) = (0, false);
+            while i < chars.len() {
+                let c = chars[i];
+                escaped.push(c);
+                i += 1;
+                match c {
+                    '\\' if in_string => {
+                        if let Some(&c) = chars.get(i) {
+                            escaped.push(c);
+                            i += 1;
+                        }
+                    }
+                    '"' => in_string = !in_string,
+                    '{' if !in_string => depth += 1,
+                    '}' if !in_string => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        } else {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '"' => escaped.push_str("\\\""),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                c => escaped.push(c),
+            }
+            i += 1;
+        }
+    }
+    escaped
+}
 
-        Value::Object(map)
+fn templates(values: &[String]) -> Expression {
+    values.iter().map(|value| template(value)).collect()
+}
+
+/// Convert a value from `.bakehouse` to HCL, treating strings as templates as bake does in JSON
+fn expression(value: &serde_json::Value) -> Expression {
+    match value {
+        serde_json::Value::Null => Expression::Null,
+        serde_json::Value::Bool(value) => Expression::Bool(*value),
+        serde_json::Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(value), _) => value.into(),
+            (None, Some(value)) => value.into(),
+            _ => number.as_f64().unwrap_or_default().into(),
+        },
+        serde_json::Value::String(value) => template(value),
+        serde_json::Value::Array(values) => values.iter().map(expression).collect(),
+        serde_json::Value::Object(map) => Expression::Object(
+            map.iter()
+                .map(|(key, value)| (ObjectKey::Expression(Expression::String(key.clone())), expression(value)))
+                .collect(),
+        ),
     }
 }
 
+/// Escape `${` and `%{` so bake reads a value literally rather than as a template
+pub fn escape_template(value: &str) -> String {
+    value.replace("${", "$${").replace("%{", "%%{")
+}
+
+fn context_value(value: &str) -> Expression {
+    match value.starts_with("target:") {
+        true => template(value),
+        false => Expression::String(value.to_string()),
+    }
+}
+
+/// JSON strings are templates to bake, so literal context paths need their `${` and `%{` escaped
+fn contexts_map<S: serde::Serializer>(map: &BTreeMap<String, String>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().map(|(key, value)| match value.starts_with("target:") {
+        true => (key, value.clone()),
+        false => (key, escape_template(value)),
+    }))
+}
+
+/// Bake target and group names may only contain letters, digits, `-` and `_`
+pub fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
 #[derive(Debug, Serialize)]
 pub struct BakeFile {
-    pub group: HashMap<String, Group>,
-    pub target: HashMap<String, Target>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub variable: BTreeMap<String, Variable>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub group: BTreeMap<String, Group>,
+    pub target: BTreeMap<String, Target>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Variable {
+    pub default: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -93,85 +387,512 @@ pub struct Group {
 impl BakeFile {
     pub fn new() -> Self {
         BakeFile {
-            group: HashMap::new(),
-            target: HashMap::new(),
+            variable: BTreeMap::new(),
+            group: BTreeMap::new(),
+            target: BTreeMap::new(),
         }
     }
 
     pub fn add_target(&mut self, name: String, mut target: Target) {
         // If we have dockerfile contents, write them to a file
-        if let Some(contents) = target.dockerfile_contents.take() {
-            let dockerfile_path = PathBuf::from(&target.context).join(&target.dockerfile);
+        if let (Some(contents), Some(context), Some(dockerfile)) =
+            (target.dockerfile_contents.take(), &target.context, &target.dockerfile)
+        {
+            let dockerfile_path = PathBuf::from(context).join(dockerfile);
             fs::write(dockerfile_path, contents).expect("Failed to write Dockerfile");
         }
 
         self.target.insert(name, target);
     }
 
+    /// A target's named contexts, its own merged over those it inherits
+    pub fn contexts(&self, name: &str) -> BTreeMap<String, String> {
+        let Some(target) = self.target.get(name) else {
+            return BTreeMap::new();
+        };
+        let mut contexts: BTreeMap<String, String> = target
+            .inherits
+            .iter()
+            .flat_map(|parent| self.contexts(parent))
+            .collect();
+        contexts.extend(target.contexts.clone());
+        contexts
+    }
+
+    /// Let every target also read the caches of the targets it depends on
+    pub fn chain_caches(&mut self) {
+        let mut chained = BTreeMap::new();
+        for name in self.target.keys() {
+            self.chained_cache(name, &mut chained);
+        }
+        for (name, cache_from) in chained {
+            if let Some(target) = self.target.get_mut(&name) {
+                target.cache_from = cache_from;
+            }
+        }
+    }
+
+    /// A target's `cache-from` with the caches of its dependencies, including inherited ones.
+    /// A target setting its own list replaces the inherited one, so it takes that along too,
+    /// while a target without one keeps inheriting it as it is
+    fn chained_cache(&self, name: &str, chained: &mut BTreeMap<String, Vec<String>>) -> Vec<String> {
+        if let Some(cache_from) = chained.get(name) {
+            return cache_from.clone();
+        }
+        let Some(target) = self.target.get(name) else {
+            return vec![];
+        };
+
+        let mut cache_from = target.cache_from.clone();
+        if !cache_from.is_empty() {
+            let mut entries: Vec<String> = Vec::new();
+            for parent in &target.inherits {
+                entries.extend(self.chained_cache(parent, chained));
+            }
+            for dep in self.contexts(name).values().filter_map(|context| context.strip_prefix("target:")) {
+                entries.extend(self.target.get(dep).map(|dep| dep.cache_from.clone()).unwrap_or_default());
+            }
+            for entry in entries {
+                if !cache_from.contains(&entry) {
+                    cache_from.push(entry);
+                }
+            }
+        }
+
+        chained.insert(name.to_string(), cache_from.clone());
+        cache_from
+    }
+
+    pub fn add_variable(&mut self, name: String, default: String) {
+        self.variable.insert(name, Variable { default });
+    }
+
     pub fn add_group(&mut self, name: String, targets: Vec<String>) {
         self.group.insert(name, Group { targets });
     }
 
-    pub fn to_hcl(&self) -> String {
-        let mut output = String::new();
+    /// Add a group, unless a target or another group already has its name
+    pub fn add_unique_group(&mut self, name: String, targets: Vec<String>) -> Result<()> {
+        if self.target.contains_key(&name) {
+            bail!("Group {} has the same name as a target", name);
+        }
+        if self.group.contains_key(&name) {
+            bail!("Group {} has the same name as another group", name);
+        }
+        self.add_group(name, targets);
+        Ok(())
+    }
 
-        // Add groups
-        for (name, group) in &self.group {
-            output.push_str(&format!("group \"{}\" {{\n", name));
-            output.push_str("  targets = [");
-            output.push_str(
-                &group
-                    .targets
-                    .iter()
-                    .map(|t| format!("\"{}\"", t))
-                    .collect::<Vec<_>>()
-                    .join(", "),
-            );
-            output.push_str("]\n}\n\n");
-        }
-
-        // Add targets
-        for (name, target) in &self.target {
-            output.push_str(&format!("target \"{}\" {{\n", name));
-            output.push_str(&format!("  context = \"{}\"\n", target.context));
-            output.push_str(&format!("  dockerfile = \"{}\"\n", target.dockerfile));
-
-            output.push_str("  tags = [");
-            output.push_str(
-                &target
-                    .tags
-                    .iter()
-                    .map(|t| format!("\"{}\"", t))
-                    .collect::<Vec<_>>()
-                    .join(", "),
-            );
-            output.push_str("]\n");
-
-            if !target.depends_on.is_empty() {
-                output.push_str("  depends_on = [");
-                output.push_str(
-                    &target
-                        .depends_on
-                        .iter()
-                        .map(|d| format!("\"{}\"", d))
-                        .collect::<Vec<_>>()
-                        .join(", "),
-                );
-                output.push_str("]\n");
-            }
+    /// Variables, groups then targets, each sorted by name, so the output only changes when
+    /// the workspace does
+    pub fn to_body(&self) -> Body {
+        let variables = self.variable.iter().map(|(name, variable)| {
+            Block::builder("variable")
+                .add_label(name.as_str())
+                .add_attribute(("default", variable.default.as_str()))
+                .build()
+        });
+        let groups = self.group.iter().map(|(name, group)| {
+            Block::builder("group")
+                .add_label(name.as_str())
+                .add_attribute(("targets", group.targets.clone()))
+                .build()
+        });
+        let targets = self.target.iter().map(|(name, target)| target.to_block(name));
+
+        Body::builder()
+            .add_blocks(variables)
+            .add_blocks(groups)
+            .add_blocks(targets)
+            .build()
+    }
+
+    pub fn to_hcl(&self) -> Result<String> {
+        let mut formatter = Formatter::builder().compact_arrays(true).build_vec();
+        Ok(self.to_body().format_string(&mut formatter)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)? + "\n")
+    }
+
+    pub fn render(&self, format: OutputFormat) -> Result<String> {
+        match format {
+            OutputFormat::Hcl => self.to_hcl(),
+            OutputFormat::Json => self.to_json(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hcl::eval::{Context, Evaluate};
+
+    fn bake_file() -> BakeFile {
+        let mut bake_file = BakeFile::new();
+        bake_file.add_variable("REGISTRY".to_string(), "".to_string());
+        bake_file.add_variable("TAG".to_string(), "".to_string());
+        bake_file.add_target(
+            "_common".to_string(),
+            Target::base(BTreeMap::from([
+                ("pull".to_string(), serde_json::json!(true)),
+                ("platforms".to_string(), serde_json::json!(["linux/amd64", "linux/arm64"])),
+                (
+                    "labels".to_string(),
+                    serde_json::json!({"org.opencontainers.image.version": "${TAG}", "org.opencontainers.image.vendor": "Acme"}),
+                ),
+            ])),
+        );
+        let mut api = Target::new(
+            Path::new("/repo/apps/api"),
+            Path::new("/repo"),
+            "Dockerfile.bake".to_string(),
+            vec![
+                render_pattern(DEFAULT_TAG, "api", "1.0.0"),
+                render_pattern("${REGISTRY}/${name}:latest", "api", "1.0.0"),
+            ],
+            BTreeMap::from([
+                ("base".to_string(), "target:base".to_string()),
+                ("@scope/x".to_string(), "weird\"ctx\\${x}".to_string()),
+            ]),
+        );
+        api.inherits = vec!["_common".to_string()];
+        api.cache_from = vec!["type=registry,ref=${REGISTRY}/api:buildcache".to_string()];
+        api.cache_to = vec!["type=registry,ref=${REGISTRY}/api:buildcache,mode=max".to_string()];
+        api.attest = vec!["type=sbom".to_string(), "type=provenance,mode=max".to_string()];
+        bake_file.add_target("api".to_string(), api);
+        let mut base = Target::new(
+            Path::new("/repo"),
+            Path::new("/repo"),
+            "Dockerfile.bake".to_string(),
+            vec!["base:1.0.0".to_string()],
+            BTreeMap::new(),
+        );
+        base.cache_from = vec!["type=gha,scope=base".to_string()];
+        base.secret = vec!["type=env,id=NPM_TOKEN,env=NPM_TOKEN".to_string()];
+        base.ssh = vec!["default".to_string()];
+        base.network = Some("host".to_string());
+        base.entitlements = vec!["network.host".to_string()];
+        bake_file.add_target("base".to_string(), base);
+        bake_file.add_group("default".to_string(), vec!["api".to_string()]);
+        bake_file.add_group("apps".to_string(), vec!["api".to_string()]);
+        bake_file
+    }
+
+    /// Evaluate the HCL output as bake would, with the given variable values
+    fn eval(bake_file: &BakeFile, registry: &str, tag: &str) -> Result<serde_json::Value> {
+        let mut context = Context::new();
+        context.declare_var("REGISTRY", registry);
+        context.declare_var("TAG", tag);
+        Ok(hcl::eval::from_str(&bake_file.to_hcl()?, &context)?)
+    }
+
+    #[test]
+    fn test_to_hcl() -> Result<()> {
+        assert_eq!(
+            bake_file().to_hcl()?,
+            r#"variable "REGISTRY" {
+  default = ""
+}
+
+variable "TAG" {
+  default = ""
+}
+
+group "apps" {
+  targets = ["api"]
+}
+
+group "default" {
+  targets = ["api"]
+}
+
+target "_common" {
+  labels = {
+    "org.opencontainers.image.vendor" = "Acme"
+    "org.opencontainers.image.version" = "${TAG}"
+  }
+  platforms = ["linux/amd64", "linux/arm64"]
+  pull = true
+}
+
+target "api" {
+  inherits = ["_common"]
+  context = "apps/api"
+  dockerfile = "Dockerfile.bake"
+  tags = ["${(REGISTRY == "") ? "" : "${REGISTRY}/"}api:${(TAG == "") ? "1.0.0" : TAG}", "${REGISTRY}/api:latest"]
+  contexts = {
+    "@scope/x" = "weird\"ctx\\$${x}"
+    "base" = "target:base"
+  }
+  cache-from = ["type=registry,ref=${REGISTRY}/api:buildcache"]
+  cache-to = ["type=registry,ref=${REGISTRY}/api:buildcache,mode=max"]
+  attest = ["type=sbom", "type=provenance,mode=max"]
+}
+
+target "base" {
+  context = "."
+  dockerfile = "Dockerfile.bake"
+  tags = ["base:1.0.0"]
+  cache-from = ["type=gha,scope=base"]
+  secret = ["type=env,id=NPM_TOKEN,env=NPM_TOKEN"]
+  ssh = ["default"]
+  network = "host"
+  entitlements = ["network.host"]
+}
+"#
+        );
+
+        Ok(())
+    }
 
-            // Add contexts if present
-            if !target.contexts.is_empty() {
-                output.push_str("  contexts = {\n");
-                for (key, value) in &target.contexts {
-                    output.push_str(&format!("    {} = \"{}\"\n", key, value));
+    #[test]
+    fn test_to_json() -> Result<()> {
+        assert_eq!(
+            bake_file().to_json()?,
+            r#"{
+  "variable": {
+    "REGISTRY": {
+      "default": ""
+    },
+    "TAG": {
+      "default": ""
+    }
+  },
+  "group": {
+    "apps": {
+      "targets": [
+        "api"
+      ]
+    },
+    "default": {
+      "targets": [
+        "api"
+      ]
+    }
+  },
+  "target": {
+    "_common": {
+      "labels": {
+        "org.opencontainers.image.vendor": "Acme",
+        "org.opencontainers.image.version": "${TAG}"
+      },
+      "platforms": [
+        "linux/amd64",
+        "linux/arm64"
+      ],
+      "pull": true
+    },
+    "api": {
+      "inherits": [
+        "_common"
+      ],
+      "context": "apps/api",
+      "dockerfile": "Dockerfile.bake",
+      "tags": [
+        "${(REGISTRY == \"\") ? \"\" : \"${REGISTRY}/\"}api:${(TAG == \"\") ? \"1.0.0\" : TAG}",
+        "${REGISTRY}/api:latest"
+      ],
+      "contexts": {
+        "@scope/x": "weird\"ctx\\$${x}",
+        "base": "target:base"
+      },
+      "cache-from": [
+        "type=registry,ref=${REGISTRY}/api:buildcache"
+      ],
+      "cache-to": [
+        "type=registry,ref=${REGISTRY}/api:buildcache,mode=max"
+      ],
+      "attest": [
+        "type=sbom",
+        "type=provenance,mode=max"
+      ]
+    },
+    "base": {
+      "context": ".",
+      "dockerfile": "Dockerfile.bake",
+      "tags": [
+        "base:1.0.0"
+      ],
+      "cache-from": [
+        "type=gha,scope=base"
+      ],
+      "secret": [
+        "type=env,id=NPM_TOKEN,env=NPM_TOKEN"
+      ],
+      "ssh": [
+        "default"
+      ],
+      "network": "host",
+      "entitlements": [
+        "network.host"
+      ]
+    }
+  }
+}
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_use_variables() -> Result<()> {
+        let bake_file = bake_file();
+
+        let defaults = eval(&bake_file, "", "")?;
+        assert_eq!(defaults["target"]["api"]["tags"][0], "api:1.0.0");
+
+        let overridden = eval(&bake_file, "ghcr.io/acme", "sha-abc123")?;
+        assert_eq!(overridden["target"]["api"]["tags"][0], "ghcr.io/acme/api:sha-abc123");
+        assert_eq!(overridden["target"]["api"]["tags"][1], "ghcr.io/acme/api:latest");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_placeholders() -> Result<()> {
+        assert_eq!(
+            env_placeholders("${env:NPM_TOKEN}:${env:CI}")?,
+            ("${NPM_TOKEN}:${CI}".to_string(), vec!["NPM_TOKEN".to_string(), "CI".to_string()])
+        );
+        assert_eq!(env_placeholders("${TAG}")?, ("${TAG}".to_string(), vec![]));
+        assert!(env_placeholders("${env:NPM-TOKEN}").is_err());
+        assert!(env_placeholders("${env:NPM_TOKEN").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_placeholders_in_quoted_args() -> Result<()> {
+        let (value, variables) = env_placeholders(r#"{"token":"${env:NPM_TOKEN}"}"#)?;
+        assert_eq!(variables, ["NPM_TOKEN"]);
+
+        let mut bake_file = BakeFile::new();
+        let target = Target { args: BTreeMap::from([("NPMRC".to_string(), value)]), ..Default::default() };
+        bake_file.add_target("api".to_string(), target);
+
+        let mut context = Context::new();
+        context.declare_var("NPM_TOKEN", "secret");
+        let value: serde_json::Value = hcl::eval::from_str(&bake_file.to_hcl()?, &context)?;
+        assert_eq!(value["target"]["api"]["args"]["NPMRC"], r#"{"token":"secret"}"#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_unique_group() {
+        let mut bake_file = BakeFile::new();
+        bake_file.add_target("web".to_string(), Target::default());
+        assert!(bake_file.add_unique_group("apps".to_string(), vec!["web".to_string()]).is_ok());
+        assert!(bake_file.add_unique_group("apps".to_string(), vec![]).is_err());
+        assert!(bake_file.add_unique_group("web".to_string(), vec![]).is_err());
+        assert_eq!(bake_file.group["apps"].targets, ["web"]);
+    }
+
+    #[test]
+    fn test_chain_caches() {
+        let mut bake_file = bake_file();
+        bake_file.chain_caches();
+        assert_eq!(
+            bake_file.target["api"].cache_from,
+            vec!["type=registry,ref=${REGISTRY}/api:buildcache", "type=gha,scope=base"]
+        );
+        assert_eq!(bake_file.target["base"].cache_from, vec!["type=gha,scope=base"]);
+
+        // A stage target keeps the dependency caches of the package it inherits from
+        let mut bake_file = self::bake_file();
+        bake_file.add_target(
+            "api-build".to_string(),
+            Target {
+                inherits: vec!["api".to_string()],
+                cache_from: vec!["type=gha,scope=api-build".to_string()],
+                ..Default::default()
+            },
+        );
+        bake_file.add_target("api-lint".to_string(), Target { inherits: vec!["api".to_string()], ..Default::default() });
+        bake_file.chain_caches();
+        assert_eq!(
+            bake_file.target["api-build"].cache_from,
+            vec![
+                "type=gha,scope=api-build",
+                "type=registry,ref=${REGISTRY}/api:buildcache",
+                "type=gha,scope=base"
+            ]
+        );
+        assert!(bake_file.target["api-lint"].cache_from.is_empty());
+    }
+
+    #[test]
+    fn test_to_json_matches_hcl() -> Result<()> {
+        // Both formats describe the same file once bake has evaluated them. Every JSON
+        // string is a template
+        let bake_file = bake_file();
+        let mut context = Context::new();
+        context.declare_var("REGISTRY", "ghcr.io/acme");
+        context.declare_var("TAG", "");
+
+        fn evaluate(value: &mut serde_json::Value, context: &Context) -> Result<()> {
+            match value {
+                serde_json::Value::String(string) => {
+                    let template = TemplateExpr::QuotedString(string.clone());
+                    *value = serde_json::to_value(template.evaluate(context)?)?;
+                }
+                serde_json::Value::Array(values) => {
+                    for value in values {
+                        evaluate(value, context)?;
+                    }
+                }
+                serde_json::Value::Object(map) => {
+                    for value in map.values_mut() {
+                        evaluate(value, context)?;
+                    }
                 }
-                output.push_str("  }\n");
+                _ => {}
             }
-
-            output.push_str("}\n\n");
+            Ok(())
         }
 
-        output
+        let mut from_json: serde_json::Value = serde_json::from_str(&bake_file.to_json()?)?;
+        evaluate(&mut from_json["target"], &context)?;
+
+        assert_eq!(from_json["target"], eval(&bake_file, "ghcr.io/acme", "")?["target"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_hcl_round_trip() -> Result<()> {
+        let bake_file = bake_file();
+        let parsed: Body = hcl::from_str(&bake_file.to_hcl()?)?;
+        assert_eq!(parsed, bake_file.to_body());
+
+        // Values come back exactly as they went in once the HCL is evaluated
+        let value = eval(&bake_file, "", "")?;
+        assert_eq!(value["target"]["api"]["contexts"]["@scope/x"], "weird\"ctx\\${x}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_escapes_literals() -> Result<()> {
+        let mut bake_file = BakeFile::new();
+        let target = Target {
+            args: BTreeMap::from([
+                ("QUOTED".to_string(), r#"say "${TAG}" \ $${x} ${(TAG == "v1") ? "a\"}" : "b"}"#.to_string()),
+                ("MULTILINE".to_string(), "${TAG}\nnext".to_string()),
+            ]),
+            ..Default::default()
+        };
+        bake_file.add_target("api".to_string(), target);
+
+        // The parser unescapes literals, so compare the evaluated values rather than the bodies
+        hcl::from_str::<Body>(&bake_file.to_hcl()?)?;
+        let value = eval(&bake_file, "", "v1")?;
+        assert_eq!(value["target"]["api"]["args"]["QUOTED"], r#"say "v1" \ ${x} a"}"#);
+        assert_eq!(value["target"]["api"]["args"]["MULTILINE"], "v1\nnext");
+
+        Ok(())
     }
 }
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::bake::render_pattern;
+
+/// Registry cache ref when `.bakehouse` doesn't set one, next to the image itself
+const DEFAULT_REF: &str = r#"${(REGISTRY == "") ? "" : "${REGISTRY}/"}${name}:buildcache"#;
+
+/// Directory local caches are written to when `.bakehouse` doesn't set one
+const DEFAULT_DIR: &str = ".buildx-cache";
+
+/// Where BuildKit stores and looks up layer caches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    /// A separate cache image per target, see [`CacheConfig::reference`]
+    Registry,
+    /// The GitHub Actions cache service, scoped per target
+    Gha,
+    /// An S3 bucket, with one cache manifest per target
+    S3,
+    /// A directory on the build host, one subdirectory per target
+    Local,
+    /// Cache metadata embedded in the pushed image itself
+    Inline,
+}
+
+/// How much of a build `cache-to` exports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// Only the final image's layers
+    Min,
+    /// Every intermediate stage too
+    #[default]
+    Max,
+}
+
+impl CacheMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CacheMode::Min => "min",
+            CacheMode::Max => "max",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    pub backend: CacheBackend,
+
+    /// Registry cache ref pattern. `${name}` is the target name, anything else is
+    /// interpolated by bake
+    #[serde(default, rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+
+    /// Directory for the local backend, relative to the workspace root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+
+    /// Bucket and region for the S3 backend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    #[serde(default)]
+    pub mode: CacheMode,
+
+    /// Whether targets also read the caches of the targets they depend on
+    #[serde(default = "default_dependencies")]
+    pub dependencies: bool,
+}
+
+fn default_dependencies() -> bool {
+    true
+}
+
+impl CacheConfig {
+    /// `cache-from` entries for a target. `image` is the target's first tag, which the
+    /// inline backend reads its cache from
+    pub fn cache_from(&self, name: &str, image: Option<&str>) -> Vec<String> {
+        match self.backend {
+            CacheBackend::Inline => image
+                .map(|image| format!("type=registry,ref={}", image))
+                .into_iter()
+                .collect(),
+            _ => vec![self.entry(name, "src")],
+        }
+    }
+
+    /// `cache-to` entries for a target
+    pub fn cache_to(&self, name: &str) -> Vec<String> {
+        match self.backend {
+            CacheBackend::Inline => vec!["type=inline".to_string()],
+            _ => vec![format!("{},mode={}", self.entry(name, "dest"), self.mode.name())],
+        }
+    }
+
+    /// The backend's options for a target. `direction` names the local backend's path
+    /// option, which differs between reading and writing
+    fn entry(&self, name: &str, direction: &str) -> String {
+        match self.backend {
+            CacheBackend::Registry => format!(
+                "type=registry,ref={}",
+                render_pattern(self.reference.as_deref().unwrap_or(DEFAULT_REF), name, "")
+            ),
+            CacheBackend::Gha => format!("type=gha,scope={}", name),
+            CacheBackend::S3 => {
+                let mut entry = format!("type=s3,name={}", name);
+                for (key, value) in [("bucket", &self.bucket), ("region", &self.region)] {
+                    if let Some(value) = value {
+                        entry.push_str(&format!(",{}={}", key, value));
+                    }
+                }
+                entry
+            }
+            CacheBackend::Local => format!(
+                "type=local,{}={}/{}",
+                direction,
+                self.dir.as_deref().unwrap_or(DEFAULT_DIR).trim_end_matches('/'),
+                name
+            ),
+            CacheBackend::Inline => "type=inline".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> CacheConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_cache_entries() {
+        let registry = config("backend: registry\nref: ghcr.io/acme/cache:${name}");
+        assert_eq!(registry.cache_from("api", None), vec!["type=registry,ref=ghcr.io/acme/cache:api"]);
+        assert_eq!(registry.cache_to("api"), vec!["type=registry,ref=ghcr.io/acme/cache:api,mode=max"]);
+
+        let gha = config("backend: gha\nmode: min");
+        assert_eq!(gha.cache_from("api", None), vec!["type=gha,scope=api"]);
+        assert_eq!(gha.cache_to("api"), vec!["type=gha,scope=api,mode=min"]);
+
+        let s3 = config("backend: s3\nbucket: builds\nregion: eu-west-1");
+        assert_eq!(s3.cache_from("api", None), vec!["type=s3,name=api,bucket=builds,region=eu-west-1"]);
+
+        let local = config("backend: local\ndir: tmp/cache/");
+        assert_eq!(local.cache_from("api", None), vec!["type=local,src=tmp/cache/api"]);
+        assert_eq!(local.cache_to("api"), vec!["type=local,dest=tmp/cache/api,mode=max"]);
+
+        let inline = config("backend: inline");
+        assert_eq!(inline.cache_from("api", Some("api:1.0.0")), vec!["type=registry,ref=api:1.0.0"]);
+        assert_eq!(inline.cache_to("api"), vec!["type=inline"]);
+        assert!(inline.dependencies);
+
+        assert!(serde_yaml::from_str::<CacheConfig>("backend: gha\nmode: all").is_err());
+    }
+}
@@ -1,36 +1,263 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::attest::AttestConfig;
+use crate::bake::{env_placeholders, sanitize_name, OutputFormat};
+use crate::cache::CacheConfig;
+use crate::groups::GroupConfig;
+use crate::labels::OciConfig;
+use crate::matrix::MatrixConfig;
+use crate::merge::WriteMode;
+use crate::output::OutputConfig;
+use crate::partition::PartitionConfig;
+use crate::resolvers::{pnpm::tsconfig::ReferenceMode, ResolverKind};
+use crate::secrets::{validate_ssh, Entitlement, Network, SecretConfig};
+use crate::templates;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct BakehouseConfig {
-    /// The default output format for docker-bake files (hcl or json)
-    #[serde(default = "default_output_format")]
-    pub output_format: String,
+    /// The default output format for docker-bake files (hcl or json).
+    /// The `--format` flag takes precedence
+    #[serde(default)]
+    pub output_format: OutputFormat,
+
+    /// What happens to an existing bake file: `overwrite` it, `merge` into it keeping the
+    /// blocks written by hand, or write a `separate` generated file
+    #[serde(default)]
+    pub write_mode: WriteMode,
 
     /// Custom Dockerfile template mappings
     /// The key is a glob pattern that matches package paths
-    /// The value is the path to the Dockerfile template to use
+    /// The value is the path to the Dockerfile template to use, relative to the workspace root.
+    /// The most specific matching glob wins, and the later one of equally specific globs
+    #[serde(default)]
+    pub templates: IndexMap<String, PathBuf>,
+
+    /// Resolvers to run, in order. When empty every ecosystem found in the
+    /// workspace is detected automatically
+    #[serde(default)]
+    pub resolvers: Vec<ResolverConfig>,
+
+    /// Extra dependencies between targets that no resolver can see, e.g. across
+    /// ecosystems. The key is the dependent target, the value its dependencies
+    #[serde(default)]
+    pub edges: HashMap<String, Vec<String>>,
+
+    /// Whether to check `tsconfig.json` project references against `package.json`
+    /// dependencies (off, report or add)
+    #[serde(default)]
+    pub typescript_references: ReferenceMode,
+
+    /// Defaults for bake variables, which CI can override through the environment.
+    /// `REGISTRY` and `TAG` are always declared as the default tag pattern uses them
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+
+    /// Tag patterns by package glob, relative to the workspace root. The last matching
+    /// pattern wins. `${name}` and `${version}` are filled in per package, anything
+    /// else is left for bake to interpolate
+    #[serde(default)]
+    pub tags: IndexMap<String, Vec<String>>,
+
+    /// Base targets holding shared settings, which every matching package target
+    /// `inherits` from in the order they're listed
+    #[serde(default)]
+    pub bases: IndexMap<String, BaseConfig>,
+
+    /// Build cache backend for every target. No cache is configured when unset
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+
+    /// SBOM and provenance attestations for every target, and whether to write a script
+    /// that signs the pushed images. No attestations when unset
     #[serde(default)]
-    pub templates: HashMap<String, PathBuf>,
+    pub attest: Option<AttestConfig>,
+
+    /// OCI labels and annotations from package metadata, git and CODEOWNERS. None when unset
+    #[serde(default)]
+    pub oci: Option<OciConfig>,
+
+    /// Build arguments for every target. Values are templates, and `${env:VAR}` reads
+    /// `VAR` from the environment when bake runs
+    #[serde(default)]
+    pub args: BTreeMap<String, String>,
+
+    /// Platforms every target is built for, e.g. `linux/amd64`. Targets are built for
+    /// the host platform when empty
+    #[serde(default)]
+    pub platforms: Vec<String>,
+
+    /// Settings for the packages matching each glob, relative to the workspace root.
+    /// Later matches override earlier ones, and a package's own settings (the
+    /// `bakehouse` key in package.json) override them all
+    #[serde(default)]
+    pub packages: IndexMap<String, PackageSettings>,
+
+    /// Bake groups by name, each holding the packages that match its glob or tags
+    #[serde(default)]
+    pub groups: IndexMap<String, GroupConfig>,
+
+    /// Whether to add a `lint` group running buildx's Dockerfile build checks on every
+    /// package target
+    #[serde(default)]
+    pub lint: bool,
+
+    /// Slices of the workspace, by name, that also get bake files of their own
+    #[serde(default)]
+    pub partitions: IndexMap<String, PartitionConfig>,
+
+    /// Where partition bake files are written, relative to the workspace root. `bake` when unset
+    #[serde(default)]
+    pub partition_dir: Option<PathBuf>,
+
+    /// Matrix fan-outs by name, e.g. `test` over Node versions. Each adds a matrix target
+    /// per matching package and the groups to build it
+    #[serde(default)]
+    pub matrix: IndexMap<String, MatrixConfig>,
 }
 
-fn default_output_format() -> String {
-    "hcl".to_string()
+/// Build settings that can be given per package
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PackageSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<Vec<String>>,
+
+    /// Where bake exports the build to instead of the image store. A package whose outputs
+    /// are all files builds its Dockerfile's `export` stage, when it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Vec<OutputConfig>>,
+
+    /// Whether the package is an app that gets deployed, which gives it an `<app>-all` group
+    /// and a place in the `deployables` group. Nx projects with a container target are
+    /// deployable unless this is set to false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployable: Option<bool>,
+
+    /// Build arguments, added to those of the workspace and earlier matches
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+
+    /// Secrets build steps can mount, e.g. an `.npmrc` or a token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<Vec<SecretConfig>>,
+
+    /// SSH agents or keys forwarded to the build, e.g. to fetch private git dependencies.
+    /// `default` is the agent at `SSH_AUTH_SOCK`, `<id>=<socket or keys>` anything else
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh: Option<Vec<String>>,
+
+    /// Networking for `RUN` steps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<Network>,
+
+    /// Privileges the build needs, granted with `docker buildx bake --allow`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entitlements: Option<Vec<Entitlement>>,
+
+    /// Dockerfile stages that also get a `<target>-<stage>` target of their own, e.g. `deps`
+    /// or `test`. Defaults to those in the template's front matter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stages: Option<Vec<String>>,
+
+    /// The stage, one of `stages`, that dependent packages copy from instead of the final image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependency_stage: Option<String>,
 }
 
-impl Default for BakehouseConfig {
-    fn default() -> Self {
-        Self {
-            output_format: default_output_format(),
-            templates: HashMap::new(),
+impl PackageSettings {
+    /// Layer `other` on top of these settings, field by field
+    pub fn merge(&mut self, other: &PackageSettings) {
+        if other.platforms.is_some() {
+            self.platforms.clone_from(&other.platforms);
+        }
+        if other.output.is_some() {
+            self.output.clone_from(&other.output);
+        }
+        if other.deployable.is_some() {
+            self.deployable = other.deployable;
+        }
+        self.args.extend(other.args.clone());
+        if other.secrets.is_some() {
+            self.secrets.clone_from(&other.secrets);
+        }
+        if other.ssh.is_some() {
+            self.ssh.clone_from(&other.ssh);
+        }
+        if other.network.is_some() {
+            self.network = other.network;
+        }
+        if other.entitlements.is_some() {
+            self.entitlements.clone_from(&other.entitlements);
+        }
+        if other.stages.is_some() {
+            self.stages.clone_from(&other.stages);
+        }
+        if other.dependency_stage.is_some() {
+            self.dependency_stage.clone_from(&other.dependency_stage);
         }
     }
+
+    pub fn validate(&self) -> Result<()> {
+        for secret in self.secrets.iter().flatten() {
+            secret.validate()?;
+        }
+        for entry in self.ssh.iter().flatten() {
+            validate_ssh(entry)?;
+        }
+        let stages = self.stages.as_deref().unwrap_or_default();
+        if let Some(stage) = stages.iter().find(|stage| sanitize_name(stage) != **stage) {
+            bail!("Invalid stage name '{}'", stage);
+        }
+        if let Some(stage) = self.dependency_stage.as_ref().filter(|stage| !stages.contains(stage)) {
+            bail!("Dependency stage {} isn't one of the stages", stage);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BaseConfig {
+    /// Glob of package paths, relative to the workspace root, that inherit from
+    /// this base. Every package does when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<String>,
+
+    /// Bake target attributes, e.g. `platforms`, `labels` or `pull`
+    #[serde(default)]
+    pub attributes: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolverConfig {
+    /// Which resolver to run
+    pub kind: ResolverKind,
+
+    /// Directory to resolve, relative to the workspace root
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+
+    /// Target name prefix used when more than one resolver is in play
+    /// (defaults to the resolver kind)
+    #[serde(default)]
+    pub prefix: Option<String>,
+
+    /// Executable and arguments for an `external` resolver
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+
+    /// How long an `external` resolver may run, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
 }
 
 impl BakehouseConfig {
     /// Load the configuration from a .bakehouse file in the given directory
-    pub fn load(workspace_root: &PathBuf) -> Result<Self> {
+    pub fn load(workspace_root: &Path) -> Result<Self> {
         let config_path = workspace_root.join(".bakehouse");
         
         // If the file doesn't exist, return default config
@@ -39,25 +266,136 @@ impl BakehouseConfig {
         }
 
         let content = std::fs::read_to_string(config_path)?;
-        let config: BakehouseConfig = serde_yaml::from_str(&content)?;
+        let mut config: BakehouseConfig = serde_yaml::from_str(&content)?;
+
+        for (pattern, template) in config.templates.iter_mut() {
+            glob::Pattern::new(pattern).with_context(|| format!("Invalid glob '{}'", pattern))?;
+            *template = workspace_root.join(&*template);
+            if !template.is_file() {
+                bail!("Template {} for '{}' doesn't exist", template.display(), pattern);
+            }
+        }
+        for pattern in config.tags.keys().chain(config.packages.keys()) {
+            glob::Pattern::new(pattern).with_context(|| format!("Invalid glob '{}'", pattern))?;
+        }
+        for value in config.args.values().chain(config.packages.values().flat_map(|settings| settings.args.values())) {
+            env_placeholders(value)?;
+        }
+        for (pattern, settings) in &config.packages {
+            settings
+                .validate()
+                .with_context(|| format!("Invalid settings for packages '{}'", pattern))?;
+        }
+        for (name, partition) in &config.partitions {
+            partition.validate(name)?;
+        }
+        for (name, group) in &config.groups {
+            group.validate(name)?;
+        }
+        for (name, matrix) in &config.matrix {
+            matrix.validate(name)?;
+        }
+        for (name, base) in &config.bases {
+            if sanitize_name(name) != *name {
+                bail!("Invalid base target name '{}'", name);
+            }
+            if let Some(pattern) = &base.packages {
+                glob::Pattern::new(pattern)
+                    .with_context(|| format!("Invalid glob '{}' in base {}", pattern, name))?;
+            }
+        }
 
         Ok(config)
     }
 
-    /// Find a matching template for a given package path
-    pub fn find_template(&self, package_path: &PathBuf) -> Option<&PathBuf> {
-        for (glob, template) in &self.templates {
-            if glob::Pattern::new(glob)
-                .ok()?
-                .matches_path(package_path)
-            {
-                return Some(template);
+    /// Names of the bases a package inherits from, by its path relative to the workspace root
+    pub fn find_bases(&self, package_path: &Path) -> Vec<String> {
+        self.bases
+            .iter()
+            .filter(|(_, base)| match &base.packages {
+                Some(pattern) => glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches_path(package_path)),
+                None => true,
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// A package's settings: the workspace's, then every matching glob's, then its own
+    pub fn package_settings(&self, package_path: &Path, own: &PackageSettings) -> PackageSettings {
+        let mut settings = PackageSettings {
+            platforms: (!self.platforms.is_empty()).then(|| self.platforms.clone()),
+            args: self.args.clone(),
+            ..Default::default()
+        };
+        for (pattern, glob_settings) in &self.packages {
+            if glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches_path(package_path)) {
+                settings.merge(glob_settings);
             }
         }
-        None
+        settings.merge(own);
+        settings
+    }
+
+    /// Whether a package, by its path relative to the workspace root, is part of a matrix
+    pub fn in_matrix(&self, matrix: &MatrixConfig, package_path: &Path) -> bool {
+        matrix
+            .packages
+            .as_ref()
+            .is_none_or(|pattern| glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches_path(package_path)))
+    }
+
+    /// Tag patterns for a package, by its path relative to the workspace root. The most
+    /// specific matching glob wins, as for templates
+    pub fn find_tags(&self, package_path: &Path) -> Option<&[String]> {
+        most_specific_match(&self.tags, package_path).map(Vec::as_slice)
+    }
+
+    /// Find a matching template for a given package path
+    pub fn find_template(&self, package_path: &Path) -> Option<&PathBuf> {
+        self.matching_templates(package_path)
+            .first()
+            .map(|(_, template)| *template)
+    }
+
+    /// Every template whose glob matches a package path, the one that applies first
+    pub fn matching_templates(&self, package_path: &Path) -> Vec<(&String, &PathBuf)> {
+        let mut matches: Vec<_> = self
+            .templates
+            .iter()
+            .enumerate()
+            .filter(|(_, (pattern, _))| {
+                *pattern != templates::ROOT_KEY
+                    && glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches_path(package_path))
+            })
+            .collect();
+        matches.sort_by_key(|(index, (pattern, _))| std::cmp::Reverse((specificity(pattern), *index)));
+        matches.into_iter().map(|(_, template)| template).collect()
     }
 }
 
+/// How specific a glob is: the number of path segments without wildcards, then the number
+/// of other literal characters
+pub fn specificity(pattern: &str) -> (usize, usize) {
+    let is_wildcard = |c: char| matches!(c, '*' | '?' | '[' | ']');
+    let segments = pattern
+        .split('/')
+        .filter(|segment| !segment.contains(is_wildcard))
+        .count();
+    let characters = pattern.chars().filter(|c| !is_wildcard(*c) && *c != '/').count();
+    (segments, characters)
+}
+
+/// The value of the most specific glob in `patterns` that matches `path`, and of equally
+/// specific ones the later
+fn most_specific_match<'a, T>(patterns: &'a IndexMap<String, T>, path: &Path) -> Option<&'a T> {
+    patterns
+        .iter()
+        .enumerate()
+        .filter(|(_, (pattern, _))| glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches_path(path)))
+        .max_by_key(|(index, (pattern, _))| (specificity(pattern), *index))
+        .map(|(_, (_, value))| value)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,7 +405,7 @@ mod tests {
     #[test]
     fn test_default_config() {
         let config = BakehouseConfig::default();
-        assert_eq!(config.output_format, "hcl");
+        assert_eq!(config.output_format, OutputFormat::Hcl);
         assert!(config.templates.is_empty());
     }
 
@@ -83,15 +421,173 @@ templates:
   "packages/*": "./templates/lib.dockerfile"
 "#;
         fs::write(&config_path, config_content)?;
+        fs::create_dir(temp_dir.path().join("templates"))?;
+        fs::write(temp_dir.path().join("templates/app.dockerfile"), "FROM {{ root }}\n")?;
+
+        // Every template has to exist
+        let error = BakehouseConfig::load(temp_dir.path()).unwrap_err();
+        assert!(error.to_string().ends_with("lib.dockerfile for 'packages/*' doesn't exist"));
 
-        let config = BakehouseConfig::load(&temp_dir.path().to_path_buf())?;
-        assert_eq!(config.output_format, "json");
+        fs::write(temp_dir.path().join("templates/lib.dockerfile"), "FROM {{ root }}\n")?;
+        let config = BakehouseConfig::load(temp_dir.path())?;
+        assert_eq!(config.output_format, OutputFormat::Json);
         assert_eq!(config.templates.len(), 2);
         assert_eq!(
             config.templates.get("apps/*").unwrap(),
-            &PathBuf::from("./templates/app.dockerfile")
+            &temp_dir.path().join("./templates/app.dockerfile")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_resolvers_and_edges() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config_content = r#"
+resolvers:
+  - kind: pnpm
+  - kind: cargo
+    path: rust
+    prefix: rs
+  - kind: external
+    command: ["./tools/resolve.sh", "--json"]
+    timeout: 10
+edges:
+  pnpm-admin:
+    - rs-wasm-core
+"#;
+        fs::write(temp_dir.path().join(".bakehouse"), config_content)?;
+
+        let config = BakehouseConfig::load(temp_dir.path())?;
+        assert_eq!(
+            config.resolvers,
+            vec![
+                ResolverConfig {
+                    kind: ResolverKind::Pnpm,
+                    path: None,
+                    prefix: None,
+                    command: vec![],
+                    timeout: None,
+                },
+                ResolverConfig {
+                    kind: ResolverKind::Cargo,
+                    path: Some(PathBuf::from("rust")),
+                    prefix: Some("rs".to_string()),
+                    command: vec![],
+                    timeout: None,
+                },
+                ResolverConfig {
+                    kind: ResolverKind::External,
+                    path: None,
+                    prefix: None,
+                    command: vec!["./tools/resolve.sh".to_string(), "--json".to_string()],
+                    timeout: Some(10),
+                },
+            ]
+        );
+        assert_eq!(config.edges["pnpm-admin"], vec!["rs-wasm-core".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_tags() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config_content = r#"
+variables:
+  REGISTRY: ghcr.io/acme
+tags:
+  "apps/admin": ["${REGISTRY}/admin:${TAG}"]
+  "apps/*": ["${REGISTRY}/${name}:${TAG}", "${REGISTRY}/${name}:${version}"]
+  "**": ["${REGISTRY}/${name}:${TAG}"]
+"#;
+        fs::write(temp_dir.path().join(".bakehouse"), config_content)?;
+
+        let config = BakehouseConfig::load(temp_dir.path())?;
+        assert_eq!(config.variables["REGISTRY"], "ghcr.io/acme");
+        assert_eq!(config.find_tags(Path::new("apps/web")).unwrap().len(), 2);
+        assert_eq!(config.find_tags(Path::new("apps/admin")).unwrap(), ["${REGISTRY}/admin:${TAG}"]);
+        assert_eq!(config.find_tags(Path::new("packages/ui")).unwrap().len(), 1);
+
+        fs::write(temp_dir.path().join(".bakehouse"), "tags:\n  \"apps/[\": []\n")?;
+        assert!(BakehouseConfig::load(temp_dir.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_bases() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config_content = r#"
+bases:
+  _common:
+    attributes:
+      pull: true
+      labels:
+        org.opencontainers.image.vendor: Acme
+  _apps:
+    packages: "apps/*"
+    attributes:
+      platforms: ["linux/amd64", "linux/arm64"]
+"#;
+        fs::write(temp_dir.path().join(".bakehouse"), config_content)?;
+
+        let config = BakehouseConfig::load(temp_dir.path())?;
+        assert_eq!(config.bases["_common"].attributes["pull"], true);
+        assert_eq!(config.find_bases(Path::new("apps/web")), vec!["_common", "_apps"]);
+        assert_eq!(config.find_bases(Path::new("packages/ui")), vec!["_common"]);
+
+        fs::write(temp_dir.path().join(".bakehouse"), "bases:\n  \"common base\": {}\n")?;
+        assert!(BakehouseConfig::load(temp_dir.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_settings() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config_content = r#"
+platforms: [linux/amd64, linux/arm64]
+args:
+  NODE_ENV: production
+  NPM_TOKEN: ${env:NPM_TOKEN}
+packages:
+  "apps/*":
+    platforms: [linux/amd64]
+    args:
+      NODE_ENV: staging
+  "apps/edge":
+    platforms: [linux/arm64]
+"#;
+        fs::write(temp_dir.path().join(".bakehouse"), config_content)?;
+        let config = BakehouseConfig::load(temp_dir.path())?;
+        let none = PackageSettings::default();
+
+        let platforms = |path: &str, own: &PackageSettings| config.package_settings(Path::new(path), own).platforms;
+        assert_eq!(platforms("libs/ui", &none), Some(vec!["linux/amd64".into(), "linux/arm64".into()]));
+        assert_eq!(platforms("apps/web", &none), Some(vec!["linux/amd64".into()]));
+        assert_eq!(platforms("apps/edge", &none), Some(vec!["linux/arm64".into()]));
+
+        let own = PackageSettings {
+            platforms: Some(vec!["linux/arm/v7".into()]),
+            args: BTreeMap::from([("API_URL".into(), "/api".into())]),
+            ..Default::default()
+        };
+        assert_eq!(platforms("apps/web", &own), Some(vec!["linux/arm/v7".into()]));
+
+        // Args are merged key by key
+        assert_eq!(
+            config.package_settings(Path::new("apps/edge"), &own).args,
+            BTreeMap::from([
+                ("API_URL".into(), "/api".into()),
+                ("NODE_ENV".into(), "staging".into()),
+                ("NPM_TOKEN".into(), "${env:NPM_TOKEN}".into()),
+            ])
         );
 
+        fs::write(temp_dir.path().join(".bakehouse"), "args:\n  TOKEN: ${env:NPM-TOKEN}\n")?;
+        assert!(BakehouseConfig::load(temp_dir.path()).is_err());
+
         Ok(())
     }
 
@@ -121,6 +617,27 @@ templates:
         );
         assert!(config.find_template(&other_path).is_none());
 
+        // The most specific glob wins wherever it is, and the later of two as specific ones
+        config.templates.insert("apps/admin".to_string(), PathBuf::from("./templates/admin.dockerfile"));
+        config.templates.insert("**".to_string(), PathBuf::from("./templates/any.dockerfile"));
+        config.templates.insert("apps/my-*".to_string(), PathBuf::from("./templates/mine.dockerfile"));
+        assert_eq!(
+            config.find_template(Path::new("apps/admin")).unwrap(),
+            &PathBuf::from("./templates/admin.dockerfile")
+        );
+        assert_eq!(
+            config
+                .matching_templates(&app_path)
+                .into_iter()
+                .map(|(pattern, _)| pattern.as_str())
+                .collect::<Vec<_>>(),
+            ["apps/my-*", "apps/*", "**"]
+        );
+        assert_eq!(
+            config.find_template(&other_path).unwrap(),
+            &PathBuf::from("./templates/any.dockerfile")
+        );
+
         Ok(())
     }
 } 
\ No newline at end of file
@@ -1,27 +1,102 @@
-use anyhow::Result;
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
 use std::fs;
 use std::path::PathBuf;
 use tera::{Context, Tera};
 
+use crate::platforms;
+use crate::templates;
+
+/// Settings a template declares for the packages using it, as YAML between `---` lines at
+/// the top of the template
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatter {
+    /// Stages that get bake targets of their own
+    #[serde(default)]
+    pub stages: Option<Vec<String>>,
+
+    /// The stage dependent packages copy from
+    #[serde(default)]
+    pub dependency_stage: Option<String>,
+}
+
+impl FrontMatter {
+    /// Split a template into its front matter, if it has any, and the rest
+    fn parse(template: &str) -> Result<(Self, &str)> {
+        let Some(rest) = template.strip_prefix("---\n") else {
+            return Ok((Self::default(), template));
+        };
+        let Some(end) = rest.find("\n---\n") else {
+            anyhow::bail!("Front matter isn't closed with `---`");
+        };
+        let front_matter = serde_yaml::from_str::<Option<Self>>(&rest[..end])?.unwrap_or_default();
+
+        Ok((front_matter, &rest[end + "\n---\n".len()..]))
+    }
+}
+
+/// Declares the build arguments from .bakehouse, for templates to `{% include "build_args" %}`
+const BUILD_ARGS: &str = r#"{%- if args %}
+
+# Build arguments from .bakehouse
+{%- for arg in args %}
+ARG {{ arg }}
+{%- endfor %}
+{%- endif %}"#;
+
 #[derive(Debug, Clone)]
 pub struct DockerfileTemplate {
     template: Tera,
     pub context: Context,
+    pub front_matter: FrontMatter,
+    /// Name of the built-in template it renders, if it isn't one from the workspace
+    pub builtin: Option<&'static str>,
 }
 
 impl DockerfileTemplate {
     pub fn new(template_path: &PathBuf) -> Result<Self> {
-        let template_content = fs::read_to_string(&template_path)?;
+        let template_content = fs::read_to_string(template_path)?;
+        Self::parse(&template_content)
+            .with_context(|| format!("Invalid template {}", template_path.display()))
+    }
+
+    /// One of the templates compiled into the binary, by file name
+    pub fn builtin(name: &'static str) -> Result<Self> {
+        let content = templates::builtin(name).with_context(|| format!("No built-in template {}", name))?;
+        let mut template = Self::parse(content).with_context(|| format!("Invalid built-in template {}", name))?;
+        template.builtin = Some(name);
+        Ok(template)
+    }
+
+    fn parse(template_content: &str) -> Result<Self> {
+        let (front_matter, template_content) = FrontMatter::parse(template_content)?;
 
         let mut tera = Tera::default();
-        tera.add_raw_template("dockerfile", &template_content)?;
+        platforms::register(&mut tera);
+        tera.add_raw_templates([("build_args", BUILD_ARGS), ("dockerfile", template_content)])?;
+
+        // Build steps mount nothing unless the package has secrets or SSH agents
+        let mut context = Context::new();
+        context.insert("mounts", "");
 
         Ok(Self {
             template: tera,
-            context: Context::new(),
+            context,
+            front_matter,
+            builtin: None,
         })
     }
 
+    /// Render with another template, keeping the context the resolver built
+    pub fn replace_template(&mut self, template_path: &PathBuf) -> Result<()> {
+        let replacement = Self::new(template_path)?;
+        self.template = replacement.template;
+        self.front_matter = replacement.front_matter;
+        self.builtin = None;
+        Ok(())
+    }
+
     // pub fn generate_dockerfile(&self) -> Result<String> {
     //
 
@@ -37,3 +112,41 @@ impl DockerfileTemplate {
         Ok(self.template.render("dockerfile", &self.context)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_front_matter() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("Dockerfile.tera");
+        fs::write(
+            &path,
+            "---\nstages: [deps, build, test]\ndependency_stage: build\n---\nFROM {{ root }} AS deps\n",
+        )?;
+        let template = DockerfileTemplate::new(&path)?;
+        assert_eq!(template.front_matter.stages.as_deref(), Some(&["deps".to_string(), "build".into(), "test".into()][..]));
+        assert_eq!(template.front_matter.dependency_stage.as_deref(), Some("build"));
+
+        let mut template = template;
+        template.context.insert("root", "base");
+        assert_eq!(template.render()?, "FROM base AS deps\n");
+
+        assert_eq!(FrontMatter::parse("FROM base\n")?, (FrontMatter::default(), "FROM base\n"));
+        assert!(FrontMatter::parse("---\nstages: [deps]\nFROM base\n").is_err());
+
+        fs::write(&path, "FROM {{ root }}\nWORKDIR /app\n{%- include \"build_args\" %}\n")?;
+        let mut template = DockerfileTemplate::new(&path)?;
+        template.context.insert("root", "base");
+        template.context.insert("args", &["NPM_TOKEN"]);
+        assert_eq!(
+            template.render()?,
+            "FROM base\nWORKDIR /app\n\n# Build arguments from .bakehouse\nARG NPM_TOKEN\n"
+        );
+        assert!(FrontMatter::parse("---\nstage: deps\n---\nFROM base\n").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::bake::sanitize_name;
+use crate::workspace::Workspace;
+
+/// A named group of packages, selected by path glob, tag or both
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupConfig {
+    /// Glob of package paths, relative to the workspace root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<String>,
+
+    /// Package tags, e.g. Nx's `scope:shop`. A package with any of them is included
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl GroupConfig {
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if sanitize_name(name) != name {
+            bail!("Invalid group name '{}'", name);
+        }
+        if self.packages.is_none() && self.tags.is_empty() {
+            bail!("Group {} selects no packages, set `packages` or `tags`", name);
+        }
+        if let Some(pattern) = &self.packages {
+            glob::Pattern::new(pattern).with_context(|| format!("Invalid glob '{}' in group {}", pattern, name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a package, by its path relative to the workspace root and its tags, is in the group
+    pub fn matches(&self, package_path: &Path, tags: &[String]) -> bool {
+        let by_path = self
+            .packages
+            .as_ref()
+            .is_some_and(|pattern| glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches_path(package_path)));
+        by_path || tags.iter().any(|tag| self.tags.contains(tag))
+    }
+}
+
+/// A group per top-level directory holding packages, e.g. `apps` and `packages`
+pub fn directory_groups(packages: impl IntoIterator<Item = (String, String)>) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, path) in packages {
+        let Some((directory, _)) = path.split_once('/') else {
+            continue;
+        };
+        groups.entry(sanitize_name(directory)).or_default().push(name);
+    }
+    for targets in groups.values_mut() {
+        targets.sort();
+    }
+    groups
+}
+
+/// A package and everything it's built from, directly or not, including the root
+pub fn closure(workspace: &Workspace, name: &str) -> Vec<String> {
+    let mut closure = BTreeSet::new();
+    let mut pending = vec![name.to_string()];
+    while let Some(name) = pending.pop() {
+        if closure.insert(name.clone()) {
+            pending.extend(workspace.get_dependencies(&name).into_iter().map(|(dep, _)| dep));
+        }
+    }
+    closure.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_config() {
+        let group: GroupConfig = serde_yaml::from_str("packages: \"apps/shop-*\"\ntags: [\"scope:shop\"]").unwrap();
+        assert!(group.validate("shop").is_ok());
+        assert!(group.validate("the shop").is_err());
+        assert!(GroupConfig::default().validate("shop").is_err());
+
+        assert!(group.matches(Path::new("apps/shop-web"), &[]));
+        assert!(group.matches(Path::new("libs/cart"), &["scope:shop".to_string()]));
+        assert!(!group.matches(Path::new("apps/admin"), &["scope:admin".to_string()]));
+    }
+
+    #[test]
+    fn test_directory_groups() {
+        let groups = directory_groups([
+            ("web".to_string(), "apps/web".to_string()),
+            ("api".to_string(), "apps/api".to_string()),
+            ("ui".to_string(), "packages/ui".to_string()),
+            ("tool".to_string(), "tool".to_string()),
+        ]);
+        assert_eq!(
+            groups,
+            BTreeMap::from([
+                ("apps".to_string(), vec!["api".to_string(), "web".to_string()]),
+                ("packages".to_string(), vec!["ui".to_string()]),
+            ])
+        );
+    }
+}
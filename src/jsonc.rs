@@ -0,0 +1,92 @@
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+
+/// Parse JSON with comments and trailing commas, as accepted by `tsconfig.json` and `turbo.json`
+pub fn from_str<T: DeserializeOwned>(content: &str) -> Result<T> {
+    Ok(serde_json::from_str(&strip(content))?)
+}
+
+/// Remove `//` and `/* */` comments and trailing commas, leaving strings untouched
+fn strip(content: &str) -> String {
+    let without_comments = scan(content, |c, rest, output| match (c, rest.peek()) {
+        ('/', Some('/')) => {
+            while rest.next_if(|c| *c != '\n').is_some() {}
+        }
+        ('/', Some('*')) => {
+            rest.next();
+            while let Some(c) = rest.next() {
+                if c == '*' && rest.next_if_eq(&'/').is_some() {
+                    break;
+                }
+            }
+        }
+        _ => output.push(c),
+    });
+
+    scan(&without_comments, |c, rest, output| {
+        let closes = || {
+            rest.clone()
+                .find(|c| !c.is_whitespace())
+                .is_some_and(|c| c == '}' || c == ']')
+        };
+        if c != ',' || !closes() {
+            output.push(c);
+        }
+    })
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Copy `content`, handing every character outside a string literal to `handle`
+fn scan(content: &str, mut handle: impl FnMut(char, &mut Chars, &mut String)) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                '\\' => output.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+            output.push(c);
+        } else {
+            handle(c, &mut chars, &mut output);
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() -> Result<()> {
+        let value: serde_json::Value = from_str(
+            r#"{
+                // line comment
+                "extends": "./base.json", /* block
+                comment */
+                "url": "http://example.com/*not a comment*/",
+                "list": [1, 2,],
+            }"#,
+        )?;
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "extends": "./base.json",
+                "url": "http://example.com/*not a comment*/",
+                "list": [1, 2],
+            })
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use crate::bake::escape_template;
+use crate::workspace::PackageMetadata;
+
+/// Prefix for labels without an `org.opencontainers.image.*` equivalent
+const LABEL_PREFIX: &str = "dev.bakehouse";
+
+/// Bake variable holding the git commit, so CI can set it without regenerating
+pub const COMMIT_VARIABLE: &str = "GIT_COMMIT";
+
+/// Where GitHub and GitLab look for CODEOWNERS, in the order they do
+const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OciConfig {
+    /// Whether to set image labels
+    #[serde(default = "default_labels")]
+    pub labels: bool,
+
+    /// Whether to also set the same keys as manifest annotations, which need an exporter
+    /// that supports them (not `--load` into the classic Docker image store)
+    #[serde(default)]
+    pub annotations: bool,
+}
+
+fn default_labels() -> bool {
+    true
+}
+
+/// Owners of each path, from the workspace's CODEOWNERS file. The last matching rule wins
+#[derive(Debug, Default)]
+pub struct CodeOwners {
+    rules: Vec<(glob::Pattern, Vec<String>)>,
+}
+
+impl CodeOwners {
+    pub fn load(workspace_root: &Path) -> Result<Self> {
+        let Some(path) = CODEOWNERS_PATHS
+            .iter()
+            .map(|path| workspace_root.join(path))
+            .find(|path| path.exists())
+        else {
+            return Ok(Self::default());
+        };
+        let content =
+            std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+        Ok(Self::parse(&content))
+    }
+
+    fn parse(content: &str) -> Self {
+        let rules = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let pattern = rule_pattern(words.next()?)?;
+                Some((pattern, words.take_while(|word| !word.starts_with('#')).map(String::from).collect()))
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Owners of a package directory, relative to the workspace root. A rule for the
+    /// directory or any directory above it applies
+    pub fn owners(&self, package_path: &Path) -> &[String] {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| {
+                package_path
+                    .ancestors()
+                    .any(|path| pattern.matches_path_with(path, options))
+            })
+            .map(|(_, owners)| owners.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// A CODEOWNERS pattern as a glob. Like `.gitignore`, a pattern is anchored to the root
+/// when it contains a `/` other than a trailing one, and matches at any depth otherwise
+fn rule_pattern(pattern: &str) -> Option<glob::Pattern> {
+    let trimmed = pattern.trim_end_matches('/');
+    let glob = match trimmed.strip_prefix('/') {
+        Some("") => "**".to_string(),
+        Some(anchored) => anchored.to_string(),
+        None if trimmed.contains('/') => trimmed.to_string(),
+        None => format!("**/{}", trimmed),
+    };
+
+    glob::Pattern::new(&glob).ok()
+}
+
+/// The commit the workspace is checked out at, if it's a git repository
+pub fn git_commit(workspace_root: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(workspace_root)
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Labels for an image, using the `org.opencontainers.image.*` keys where there is one.
+/// Values are bake templates, so everything but the commit has its `${` escaped here. Quotes and
+/// backslashes are left to the writer, since JSON and HCL escape them differently
+pub fn labels(
+    name: &str,
+    version: &str,
+    path: &Path,
+    metadata: &PackageMetadata,
+    owners: &[String],
+    dependencies: &[(String, String)],
+) -> BTreeMap<String, String> {
+    let mut labels = BTreeMap::new();
+    let mut add = |key: String, value: Option<String>| {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            labels.insert(key, escape_template(&value));
+        }
+    };
+
+    let oci = |key: &str| format!("org.opencontainers.image.{}", key);
+    add(oci("title"), Some(name.to_string()));
+    add(oci("version"), Some(version.to_string()));
+    add(oci("description"), metadata.description.clone());
+    add(oci("licenses"), metadata.license.clone());
+    add(oci("source"), metadata.repository.clone());
+    add(oci("url"), metadata.homepage.clone());
+    add(oci("authors"), metadata.author.clone());
+
+    let path = path.to_string_lossy();
+    add(format!("{}.path", LABEL_PREFIX), Some(if path.is_empty() { ".".into() } else { path.into_owned() }));
+    add(format!("{}.owners", LABEL_PREFIX), Some(owners.join(" ")));
+    add(
+        format!("{}.dependencies", LABEL_PREFIX),
+        Some(
+            dependencies
+                .iter()
+                .map(|(name, version)| format!("{}@{}", name, version))
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+    );
+
+    labels.insert(oci("revision"), format!("${{{}}}", COMMIT_VARIABLE));
+    labels
+}
+
+/// The same metadata as manifest annotations
+pub fn annotations(labels: &BTreeMap<String, String>) -> Vec<String> {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bake::{BakeFile, Target};
+    use hcl::eval::Context;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_code_owners() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir(temp_dir.path().join(".github"))?;
+        fs::write(
+            temp_dir.path().join(".github/CODEOWNERS"),
+            "# Default owners\n\
+             *       @acme/platform\n\
+             /apps/  @acme/web # web team\n\
+             /apps/admin @acme/admin @jane\n\
+             logger  @acme/observability\n",
+        )?;
+        let owners = CodeOwners::load(temp_dir.path())?;
+
+        assert_eq!(owners.owners(Path::new("packages/types")), ["@acme/platform"]);
+        assert_eq!(owners.owners(Path::new("apps/api")), ["@acme/web"]);
+        assert_eq!(owners.owners(Path::new("apps/admin")), ["@acme/admin", "@jane"]);
+        assert_eq!(owners.owners(Path::new("packages/logger")), ["@acme/observability"]);
+
+        assert!(CodeOwners::load(Path::new("/nonexistent"))?.owners(Path::new("apps/api")).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_labels() {
+        let metadata = PackageMetadata {
+            description: Some("Uses ${templates}".to_string()),
+            license: Some("MIT".to_string()),
+            repository: Some("https://github.com/acme/shop".to_string()),
+            homepage: None,
+            author: Some("Jane <jane@acme.dev>".to_string()),
+        };
+        let labels = labels(
+            "api",
+            "1.2.0",
+            Path::new("apps/api"),
+            &metadata,
+            &["@acme/web".to_string()],
+            &[("logger".to_string(), "0.1.0".to_string()), ("types".to_string(), "2.0.0".to_string())],
+        );
+
+        assert_eq!(labels["org.opencontainers.image.title"], "api");
+        assert_eq!(labels["org.opencontainers.image.version"], "1.2.0");
+        assert_eq!(labels["org.opencontainers.image.description"], "Uses $${templates}");
+        assert_eq!(labels["org.opencontainers.image.licenses"], "MIT");
+        assert_eq!(labels["org.opencontainers.image.source"], "https://github.com/acme/shop");
+        assert_eq!(labels["org.opencontainers.image.authors"], "Jane <jane@acme.dev>");
+        assert_eq!(labels["org.opencontainers.image.revision"], "${GIT_COMMIT}");
+        assert_eq!(labels["dev.bakehouse.path"], "apps/api");
+        assert_eq!(labels["dev.bakehouse.owners"], "@acme/web");
+        assert_eq!(labels["dev.bakehouse.dependencies"], "logger@0.1.0,types@2.0.0");
+        assert!(!labels.contains_key("org.opencontainers.image.url"));
+
+        assert!(annotations(&labels).contains(&"org.opencontainers.image.licenses=MIT".to_string()));
+    }
+
+    #[test]
+    fn test_labels_with_quotes() -> Result<()> {
+        let description = r#"The "shop" API, from C:\shop, using ${templates}"#;
+        let metadata = PackageMetadata { description: Some(description.to_string()), ..Default::default() };
+        let labels = labels("api", "1.2.0", Path::new("apps/api"), &metadata, &[], &[]);
+
+        let mut bake_file = BakeFile::new();
+        let target = Target { annotations: annotations(&labels), labels, ..Default::default() };
+        bake_file.add_target("api".to_string(), target);
+
+        let mut context = Context::new();
+        context.declare_var(COMMIT_VARIABLE, "abc123");
+        let value: serde_json::Value = hcl::eval::from_str(&bake_file.to_hcl()?, &context)?;
+        let target = &value["target"]["api"];
+        assert_eq!(target["labels"]["org.opencontainers.image.description"], description);
+        assert_eq!(target["labels"]["org.opencontainers.image.revision"], "abc123");
+        assert!(target["annotations"]
+            .as_array()
+            .unwrap()
+            .contains(&format!("org.opencontainers.image.description={}", description).into()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bake::{BakeFile, Target};
+
+/// Group running buildx's Dockerfile build checks on every package target
+pub const LINT_GROUP: &str = "lint";
+
+/// Add a `<name>-lint` target per target, inheriting everything but only running the build
+/// checks, and the `lint` group to run them all
+pub fn add_lint(bake_file: &mut BakeFile, targets: &[String]) -> Result<()> {
+    let mut lint_targets = Vec::new();
+    for name in targets {
+        let lint_name = format!("{}-{}", name, LINT_GROUP);
+        if bake_file.target.contains_key(&lint_name) {
+            bail!("Lint target {} has the same name as a target", lint_name);
+        }
+        bake_file.add_target(
+            lint_name.clone(),
+            Target {
+                inherits: vec![name.clone()],
+                call: Some("check".to_string()),
+                ..Default::default()
+            },
+        );
+        lint_targets.push(lint_name);
+    }
+    lint_targets.sort();
+    bake_file.add_unique_group(LINT_GROUP.to_string(), lint_targets)
+}
+
+/// What [`check`] found in a Dockerfile
+#[derive(Debug, Default, PartialEq)]
+pub struct Problems {
+    /// Mistakes bake would only report halfway through a build
+    pub errors: Vec<String>,
+    /// Things that may be mistakes but are valid, such as a bare image name in `COPY --from`
+    pub warnings: Vec<String>,
+}
+
+/// Check a Dockerfile for a missing `FROM`, a `COPY --from` index naming no earlier stage, and a
+/// workspace target used without being declared in the target's `contexts`. A `COPY --from`
+/// naming neither a stage nor a named context is pulled as an image, so it's only a warning
+pub fn check(dockerfile: &str, contexts: &BTreeMap<String, String>, workspace_targets: &BTreeSet<String>) -> Problems {
+    let mut problems = Problems::default();
+    let mut stages: Vec<Option<String>> = Vec::new();
+    let undeclared = |source: &str| workspace_targets.contains(source) && !contexts.contains_key(source);
+
+    for instruction in instructions(dockerfile) {
+        let words: Vec<_> = instruction.split_whitespace().collect();
+        let Some((keyword, words)) = words.split_first() else {
+            continue;
+        };
+        let is_stage = |source: &str| {
+            stages
+                .iter()
+                .flatten()
+                .any(|stage| stage.eq_ignore_ascii_case(source))
+        };
+
+        if keyword.eq_ignore_ascii_case("FROM") {
+            let mut words = words.iter().filter(|word| !word.starts_with("--"));
+            if let Some(image) = words.next() {
+                if undeclared(image) && !is_stage(image) {
+                    problems.errors.push(format!("FROM {} uses a workspace target that isn't a named context", image));
+                }
+            }
+            let name = match (words.next(), words.next()) {
+                (Some(word), Some(name)) if word.eq_ignore_ascii_case("AS") => Some(name.to_string()),
+                _ => None,
+            };
+            stages.push(name);
+        } else if keyword.eq_ignore_ascii_case("COPY") {
+            let Some(source) = words.iter().find_map(|word| word.strip_prefix("--from=")) else {
+                continue;
+            };
+            let is_image = source.contains([':', '/', '@', '.', '$']);
+            let index = source.parse::<usize>().ok();
+            if undeclared(source) {
+                problems.errors.push(format!(
+                    "COPY --from={} uses a workspace target that isn't a named context",
+                    source
+                ));
+            } else if index.is_some_and(|index| index + 1 >= stages.len()) {
+                problems.errors.push(format!("COPY --from={} names no earlier stage", source));
+            } else if !(is_stage(source) || index.is_some() || is_image || contexts.contains_key(source)) {
+                problems.warnings.push(format!(
+                    "COPY --from={} names no earlier stage or named context, so it's pulled as an image",
+                    source
+                ));
+            }
+        }
+    }
+    if stages.is_empty() {
+        problems.errors.push("No FROM instruction".to_string());
+    }
+
+    problems
+}
+
+/// Instructions with line continuations joined, and comments and blank lines left out
+fn instructions(dockerfile: &str) -> Vec<String> {
+    let mut instructions = Vec::new();
+    let mut current = String::new();
+    for line in dockerfile.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                current.push_str(line);
+                current.push(' ');
+            }
+            None => {
+                current.push_str(line);
+                instructions.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        instructions.push(current);
+    }
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_lint() -> Result<()> {
+        let mut bake_file = BakeFile::new();
+        bake_file.add_target("web".to_string(), Target::default());
+        bake_file.add_target("api".to_string(), Target::default());
+        add_lint(&mut bake_file, &["web".to_string(), "api".to_string()])?;
+
+        assert_eq!(bake_file.group[LINT_GROUP].targets, ["api-lint", "web-lint"]);
+        assert_eq!(bake_file.target["web-lint"].inherits, ["web"]);
+        assert_eq!(bake_file.target["web-lint"].call.as_deref(), Some("check"));
+        assert!(add_lint(&mut bake_file, &["web".to_string()]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check() {
+        let contexts = BTreeMap::from([
+            ("root".to_string(), "target:root".to_string()),
+            ("logger".to_string(), "target:logger".to_string()),
+        ]);
+        let targets = BTreeSet::from(["root".to_string(), "logger".to_string(), "types".to_string()]);
+
+        let dockerfile = "# syntax=docker/dockerfile:1\n\
+                          FROM --platform=$BUILDPLATFORM root AS build\n\
+                          COPY --from=logger /app/logger /app/logger\n\
+                          COPY --from=busybox:1.36 /bin/sh /bin/sh\n\
+                          RUN make\n\
+                          FROM scratch AS export\n\
+                          COPY --link \\\n  --from=Build /app/dist /\n\
+                          COPY --from=0 /app /app\n";
+        assert_eq!(check(dockerfile, &contexts, &targets), Problems::default());
+
+        let dockerfile = "FROM types\nCOPY --from=biuld /app /app\nCOPY --from=types /app /app\nCOPY --from=1 /app /app\n";
+        let problems = check(dockerfile, &contexts, &targets);
+        assert_eq!(
+            problems.errors,
+            [
+                "FROM types uses a workspace target that isn't a named context",
+                "COPY --from=types uses a workspace target that isn't a named context",
+                "COPY --from=1 names no earlier stage",
+            ]
+        );
+        assert_eq!(
+            problems.warnings,
+            ["COPY --from=biuld names no earlier stage or named context, so it's pulled as an image"]
+        );
+
+        assert_eq!(check("RUN make\n", &contexts, &targets).errors, ["No FROM instruction"]);
+    }
+
+    #[test]
+    fn test_check_bare_image() {
+        let dockerfile = "FROM node:20 AS build\nCOPY --from=busybox /bin/sh /bin/sh\n";
+        let problems = check(dockerfile, &BTreeMap::new(), &BTreeSet::from(["api".to_string()]));
+        assert!(problems.errors.is_empty());
+        assert_eq!(problems.warnings.len(), 1);
+    }
+}
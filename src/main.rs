@@ -1,34 +1,74 @@
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{BTreeMap, BTreeSet},
     path::PathBuf,
 };
 
-use anyhow::Result;
-use bake::Target;
-use clap::Parser;
+use anyhow::{Context, Result};
+use bake::{OutputFormat, Target};
+use clap::{Parser, Subcommand};
+mod attest;
 mod bake;
+mod cache;
 mod config;
 mod dockerfile;
+mod groups;
+mod jsonc;
+mod labels;
+mod lint;
+mod matrix;
+mod merge;
+mod output;
+mod partition;
+mod platforms;
 mod resolvers;
+mod secrets;
+mod templates;
 mod workspace;
 
-use config::BakehouseConfig;
-use workspace::Workspace;
+use config::{BakehouseConfig, PackageSettings};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
-    /// Path to the PNPM workspace root
-    #[arg(short, long, default_value = ".")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the workspace root
+    #[arg(short, long, default_value = ".", global = true)]
     workspace: PathBuf,
 
-    /// Output path for the Docker Bake file (relative to workspace root)
-    #[arg(short, long, default_value = "docker-bake.hcl")]
-    output: PathBuf,
+    /// Output path for the Docker Bake file (relative to workspace root).
+    /// Defaults to docker-bake.hcl or docker-bake.json, depending on the format, with a
+    /// `.generated` suffix when `write_mode` is `separate`
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format. Overrides `output_format` in .bakehouse
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+}
 
-    /// Output format (hcl or json)
-    #[arg(short, long, default_value = "hcl")]
-    format: String,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect and eject the Dockerfile templates
+    Templates {
+        #[command(subcommand)]
+        command: TemplatesCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TemplatesCommand {
+    /// Show which template a package uses and why
+    Which {
+        /// Target name, package name or path relative to the workspace root
+        package: String,
+    },
+    /// Copy built-in templates into the workspace and register them in .bakehouse
+    Eject {
+        /// Template to eject, e.g. `cargo.bake`. Defaults to every built-in template in use
+        name: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -37,13 +77,149 @@ async fn main() -> Result<()> {
 
     // Get absolute paths for both workspace and output
     let workspace_root = std::fs::canonicalize(&args.workspace)?;
-    let output_path = workspace_root.join(&args.output);
 
-    // Load the PNPM workspace
-    let pnpm_workspace = resolvers::pnpm::load_workspace(&workspace_root)?;
+    let config = BakehouseConfig::load(&workspace_root)?;
+
+    if let Some(Command::Templates { command }) = &args.command {
+        return match command {
+            TemplatesCommand::Which { package } => templates::which(&workspace_root, &config, package),
+            TemplatesCommand::Eject { name } => templates::eject(&workspace_root, &config, name.as_deref()),
+        };
+    }
+
+    // The command line wins over .bakehouse, which wins over the default of HCL
+    let format = args.format.unwrap_or(config.output_format);
+    let output_path = workspace_root.join(
+        args.output
+            .unwrap_or_else(|| PathBuf::from(config.write_mode.default_file_name(format))),
+    );
+
+    // Resolve every ecosystem in the workspace into a single package graph
+    let mut workspace = resolvers::load_workspace(&workspace_root, &config)?;
+
+    // Package settings in .bakehouse are matched on paths relative to the workspace root
+    let relative = |path: &std::path::Path| path.strip_prefix(&workspace_root).unwrap_or(path).to_path_buf();
+
+    // Settings come from the package itself, a matching glob or the workspace, in that order,
+    // with the stages falling back to those the template declares
+    let with_front_matter = |mut settings: PackageSettings, template: &dockerfile::DockerfileTemplate| {
+        if settings.stages.is_none() {
+            settings.stages.clone_from(&template.front_matter.stages);
+        }
+        if settings.dependency_stage.is_none() {
+            settings.dependency_stage.clone_from(&template.front_matter.dependency_stage);
+        }
+        settings
+    };
+    let mut settings: BTreeMap<String, PackageSettings> = workspace
+        .packages
+        .iter()
+        .map(|(name, package)| {
+            let settings = config.package_settings(&relative(&package.path), &package.settings);
+            (name.clone(), with_front_matter(settings, &package.dockerfile_template))
+        })
+        .collect();
+    let root_settings = with_front_matter(
+        config.package_settings(&relative(&workspace.path), &PackageSettings::default()),
+        &workspace.dockerfile_template,
+    );
+
+    // The root image is shared by every package, so unless the workspace sets its platforms
+    // it's built for all of theirs
+    let mut platforms: BTreeMap<String, Vec<String>> = settings
+        .iter_mut()
+        .map(|(name, settings)| (name.clone(), settings.platforms.take().unwrap_or_default()))
+        .collect();
+    let mut root_platforms = config.platforms.clone();
+    if root_platforms.is_empty() {
+        root_platforms = platforms.values().flatten().cloned().collect();
+        root_platforms.sort();
+        root_platforms.dedup();
+    }
+    platforms.insert(workspace.name.clone(), root_platforms);
+    settings.insert(workspace.name.clone(), root_settings);
+    for (name, package) in workspace.packages.iter_mut() {
+        if let Some(deployable) = settings[name].deployable {
+            package.deployable = deployable;
+        }
+    }
 
-    // In main(), get the root package before moving pnpm_workspace into Workspace
-    let workspace = Workspace::new(pnpm_workspace);
+    // `${env:VAR}` in build args, secrets and SSH entries becomes the bake variable `VAR`,
+    // which bake reads from the environment
+    let mut env_variables = BTreeSet::new();
+    let mut build_args: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    let mut build_secrets: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+    for (name, settings) in &settings {
+        settings
+            .validate()
+            .with_context(|| format!("Invalid settings for {}", name))?;
+        let target_args = build_args.entry(name.clone()).or_default();
+        for (arg, value) in &settings.args {
+            let (value, variables) = bake::env_placeholders(value)
+                .with_context(|| format!("Invalid build argument {} for {}", arg, name))?;
+            env_variables.extend(variables);
+            target_args.insert(arg.clone(), value);
+        }
+        let mut entries = |values: Vec<String>| -> Result<Vec<String>> {
+            values
+                .into_iter()
+                .map(|value| {
+                    let (rendered, variables) = bake::env_placeholders(&value)
+                        .with_context(|| format!("Invalid entry '{}' for {}", value, name))?;
+                    env_variables.extend(variables);
+                    Ok(rendered)
+                })
+                .collect()
+        };
+        let secret_entries = entries(settings.secrets.iter().flatten().map(secrets::SecretConfig::entry).collect())?;
+        let ssh_entries = entries(settings.ssh.clone().unwrap_or_default())?;
+        build_secrets.insert(name.clone(), (secret_entries, ssh_entries));
+    }
+
+    // A target whose outputs are all files builds the `export` stage of its Dockerfile
+    let export_only: BTreeSet<String> = settings
+        .iter()
+        .filter(|(_, settings)| {
+            settings
+                .output
+                .as_ref()
+                .is_some_and(|outputs| !outputs.is_empty() && outputs.iter().all(|output| output.is_export()))
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    // Templates get the platforms split up, to pick native binaries per TARGETPLATFORM, the
+    // names of the build args to declare, whether to add an export stage and the secrets and
+    // SSH agents to mount, along with the `RUN --mount` flags for them
+    for (name, template) in std::iter::once((&workspace.name, &mut workspace.dockerfile_template)).chain(
+        workspace
+            .packages
+            .iter_mut()
+            .map(|(name, package)| (name, &mut package.dockerfile_template)),
+    ) {
+        template
+            .context
+            .insert("platforms", &platforms::parse_all(&platforms[name])?);
+        template
+            .context
+            .insert("args", &build_args[name].keys().collect::<Vec<_>>());
+        template.context.insert("export", &export_only.contains(name));
+        let secrets = settings[name].secrets.as_deref().unwrap_or_default();
+        let ssh = settings[name].ssh.as_deref().unwrap_or_default();
+        template
+            .context
+            .insert("secrets", &secrets.iter().map(|secret| &secret.id).collect::<Vec<_>>());
+        template
+            .context
+            .insert("ssh", &ssh.iter().map(|entry| secrets::ssh_id(entry)).collect::<Vec<_>>());
+        let mounts: String = secrets
+            .iter()
+            .map(secrets::SecretConfig::mount)
+            .chain(ssh.iter().map(|entry| secrets::ssh_mount(entry)))
+            .map(|mount| mount + " ")
+            .collect();
+        template.context.insert("mounts", &mounts);
+    }
 
     // Debug: Print discovered packages
     println!("\nDiscovered packages:");
@@ -53,94 +229,413 @@ async fn main() -> Result<()> {
 
     // Debug: Print dependencies
     println!("\nPackage dependencies:");
-    for (name, _) in &workspace.packages {
-        let deps = workspace.get_dependencies(&name).into_iter().map(|(name, path)| name).collect::<Vec<_>>();
+    for name in workspace.packages.keys() {
+        let deps = workspace
+            .get_dependencies(name)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
         println!("- {} depends on: {:?}", name, deps);
     }
 
     // Create bake file
     let mut bake_file = bake::BakeFile::new();
 
-    let dockerfile_path = workspace.path.join("Dockerfile.bake");
+    // Declare the variables tags are built from, so CI can override them without regenerating
+    for name in bake::DEFAULT_VARIABLES {
+        bake_file.add_variable(name.to_string(), String::new());
+    }
+    for (name, default) in &config.variables {
+        bake_file.add_variable(name.clone(), default.clone());
+    }
+    if config.oci.is_some() {
+        let commit = labels::git_commit(&workspace_root).unwrap_or_default();
+        bake_file.add_variable(labels::COMMIT_VARIABLE.to_string(), commit);
+    }
+    for name in env_variables {
+        if !bake_file.variable.contains_key(&name) {
+            bake_file.add_variable(name, String::new());
+        }
+    }
+
+    // Shared settings live in base targets that package targets inherit from
+    for (name, base) in &config.bases {
+        if workspace.name == *name || workspace.packages.contains_key(name) {
+            anyhow::bail!("Base target {} has the same name as a package", name);
+        }
+        bake_file.add_target(name.clone(), Target::base(base.attributes.clone()));
+    }
+
+    let tags = |path: &std::path::Path, name: &str, version: &str| -> Vec<String> {
+        config
+            .find_tags(&relative(path))
+            .unwrap_or(&[bake::DEFAULT_TAG.to_string()])
+            .iter()
+            .map(|pattern| bake::render_pattern(pattern, name, version))
+            .collect()
+    };
+
+    let dockerfile_path = workspace.path.join(&workspace.dockerfile);
+
+    // Generated Dockerfiles are checked before they're written, as bake would only fail on
+    // them halfway through a build
+    let workspace_targets: BTreeSet<String> = std::iter::once(workspace.name.clone())
+        .chain(workspace.packages.keys().cloned())
+        .collect();
+    let check_dockerfile = |name: &str, dockerfile: &str, contexts: &BTreeMap<String, String>| -> Result<()> {
+        let problems = lint::check(dockerfile, contexts, &workspace_targets);
+        for warning in &problems.warnings {
+            println!("Warning: generated Dockerfile for {}: {}", name, warning);
+        }
+        if !problems.errors.is_empty() {
+            anyhow::bail!("Generated Dockerfile for {} is invalid:\n- {}", name, problems.errors.join("\n- "));
+        }
+        Ok(())
+    };
 
     // TODO - reduce this duplication!!
     // Generate Dockerfile if it doesn't exist
     if !dockerfile_path.exists() {
         // TODO - fix this clone
-        let dockerfile_content = workspace.dockerfile_template.render();
-        std::fs::write(&dockerfile_path, dockerfile_content.unwrap())?;
-        println!("Generated Dockerfile.bake for package {}", workspace.name);
+        let dockerfile_content = workspace.dockerfile_template.render()?;
+        check_dockerfile(&workspace.name, &dockerfile_content, &BTreeMap::new())?;
+        std::fs::write(&dockerfile_path, dockerfile_content)?;
+        println!("Generated {} for package {}", workspace.dockerfile, workspace.name);
     }
+    let root_dockerfile = std::fs::read_to_string(&dockerfile_path)?;
+    platforms::check_base_image(&workspace.name, &platforms[&workspace.name], &root_dockerfile)?;
 
-    bake_file.add_target(
-        workspace.name.clone(),
-        Target::new(
-            &workspace.path,
-            &workspace_root,
-            "Dockerfile.bake".to_string(),
-            vec![format!("{}:{}", &workspace.name, workspace.version)],
-            vec![],
-            HashMap::new(),
-        ),
+    // A stage target builds one stage of a package's Dockerfile, so the stage has to be there
+    let check_stages = |name: &str, dockerfile: &str| -> Result<()> {
+        for stage in settings[name].stages.iter().flatten() {
+            if !output::has_stage(dockerfile, stage) {
+                anyhow::bail!("{} has a target for its {} stage, which its Dockerfile doesn't have", name, stage);
+            }
+        }
+        Ok(())
+    };
+    check_stages(&workspace.name, &root_dockerfile)?;
+
+    let set_cache = |target: &mut Target, name: &str| {
+        if let Some(cache) = &config.cache {
+            target.cache_from = cache.cache_from(name, target.tags.first().map(String::as_str));
+            target.cache_to = cache.cache_to(name);
+        }
+    };
+    let set_output = |target: &mut Target, name: &str, dockerfile: &str| -> Result<()> {
+        let outputs = settings[name].output.as_deref().unwrap_or_default();
+        target.output = outputs.iter().map(|output| output.entry(name)).collect();
+        if export_only.contains(name) {
+            if !output::has_stage(dockerfile, output::EXPORT_STAGE) {
+                anyhow::bail!(
+                    "{} only exports files, but its Dockerfile has no {} stage to pick them",
+                    name,
+                    output::EXPORT_STAGE
+                );
+            }
+            target.target = Some(output::EXPORT_STAGE.to_string());
+        }
+        Ok(())
+    };
+    let set_secrets = |target: &mut Target, name: &str| {
+        let settings = &settings[name];
+        (target.secret, target.ssh) = build_secrets[name].clone();
+        target.network = settings.network.map(|network| network.name().to_string());
+        target.entitlements = settings
+            .entitlements
+            .iter()
+            .flatten()
+            .map(|entitlement| entitlement.name().to_string())
+            .collect();
+    };
+    let attest = config.attest.as_ref().map(|attest| attest.entries()).unwrap_or_default();
+
+    // Labels describe the package, where its source lives and what went into the image
+    let code_owners = labels::CodeOwners::load(&workspace_root)?;
+    let versions: BTreeMap<&str, &str> = std::iter::once((workspace.name.as_str(), workspace.version.as_str()))
+        .chain(workspace.packages.values().map(|package| (package.name.as_str(), package.version.as_str())))
+        .collect();
+    let set_labels = |target: &mut Target, name: &str, path: &std::path::Path, metadata: &workspace::PackageMetadata| {
+        let Some(oci) = &config.oci else {
+            return;
+        };
+        let mut dependencies: Vec<_> = target
+            .contexts
+            .keys()
+            .filter(|dep| **dep != workspace.name)
+            .map(|dep| (dep.clone(), versions.get(dep.as_str()).unwrap_or(&"").to_string()))
+            .collect();
+        dependencies.sort();
+        let labels = labels::labels(
+            name,
+            versions[name],
+            &relative(path),
+            metadata,
+            code_owners.owners(&relative(path)),
+            &dependencies,
+        );
+        if oci.annotations {
+            target.annotations = labels::annotations(&labels);
+        }
+        if oci.labels {
+            target.labels = labels;
+        }
+    };
+
+    let mut root_target = Target::new(
+        &workspace.path,
+        &workspace_root,
+        workspace.dockerfile.clone(),
+        tags(&workspace.path, &workspace.name, &workspace.version),
+        BTreeMap::new(),
     );
+    root_target.inherits = config.find_bases(&relative(&workspace.path));
+    root_target.platforms = platforms[&workspace.name].clone();
+    root_target.args = build_args[&workspace.name].clone();
+    set_cache(&mut root_target, &workspace.name);
+    root_target.attest = attest.clone();
+    set_output(&mut root_target, &workspace.name, &root_dockerfile)?;
+    set_secrets(&mut root_target, &workspace.name);
+    set_labels(&mut root_target, &workspace.name, &workspace.path, &Default::default());
+    bake_file.add_target(workspace.name.clone(), root_target);
 
     // Add targets for each package
     for (name, package) in &workspace.packages {
-        let dockerfile_path = package.path.join("Dockerfile.bake");
+        let dockerfile_path = package.path.join(&package.dockerfile);
+
+        // Each dependency is a named context, which is also how bake orders the builds. It's
+        // the dependency's final image, or the stage it's declared to be copied from
+        let contexts: BTreeMap<String, String> = workspace
+            .get_dependencies(name)
+            .into_iter()
+            .map(|(dep, _)| {
+                let context = match settings.get(&dep).and_then(|settings| settings.dependency_stage.as_ref()) {
+                    Some(stage) => format!("target:{}-{}", dep, stage),
+                    None => format!("target:{}", dep),
+                };
+                (dep, context)
+            })
+            .collect();
+        output::check_dependencies(name, &contexts, &export_only)?;
 
         // Generate Dockerfile if it doesn't exist
         if !dockerfile_path.exists() {
             // TODO - fix this clone
-            let dockerfile_content = package.dockerfile_template.render();
-            std::fs::write(&dockerfile_path, dockerfile_content.unwrap())?;
-            println!("Generated Dockerfile.bake for package {}", name);
-        }
-
-        let dependencies = workspace
-            .get_dependencies(&name)
-            .into_iter()
-            .map(|(dep, _)| dep)
-            .collect::<Vec<_>>();
-
-        let mut contexts = HashMap::new();
-        // Add all dependencies to the contexts map
-        for dep in &dependencies {
-            contexts.insert(dep.clone(), format!("target:{}", dep));
+            let dockerfile_content = package.dockerfile_template.render()?;
+            check_dockerfile(name, &dockerfile_content, &contexts)?;
+            std::fs::write(&dockerfile_path, dockerfile_content)?;
+            println!("Generated {} for package {}", package.dockerfile, name);
         }
+        let dockerfile = std::fs::read_to_string(&dockerfile_path)?;
+        platforms::check_base_image(name, &platforms[name], &dockerfile)?;
+        check_stages(name, &dockerfile)?;
 
-        let target = Target::new(
+        let mut target = Target::new(
             &package.path,
             &workspace_root,
-            "Dockerfile.bake".to_string(),
-            vec![format!("{}:{}", name, package.version)],
-            dependencies,
+            package.dockerfile.clone(),
+            tags(&package.path, name, &package.version),
             contexts,
         );
+        target.inherits = config.find_bases(&relative(&package.path));
+        target.platforms = platforms[name].clone();
+        target.args = build_args[name].clone();
+        set_cache(&mut target, name);
+        target.attest = attest.clone();
+        set_output(&mut target, name, &dockerfile)?;
+        set_secrets(&mut target, name);
+        set_labels(&mut target, name, &package.path, &package.metadata);
 
         bake_file.add_target(name.clone(), target);
     }
 
-    // Add a default group with all targets
-    bake_file.add_group(
-        "default".to_string(),
+    // Stage targets inherit everything from their package but build just the one stage, and
+    // are tagged apart so they never replace the package's image
+    for (name, package_settings) in &settings {
+        for stage in package_settings.stages.iter().flatten() {
+            let stage_name = format!("{}-{}", name, stage);
+            if bake_file.target.contains_key(&stage_name) {
+                anyhow::bail!("Stage target {} has the same name as a target", stage_name);
+            }
+            let mut target = Target {
+                inherits: vec![name.clone()],
+                target: Some(stage.clone()),
+                tags: bake_file.target[name]
+                    .tags
+                    .iter()
+                    .map(|tag| format!("{}-{}", tag, stage))
+                    .collect(),
+                ..Default::default()
+            };
+            set_cache(&mut target, &stage_name);
+            bake_file.add_target(stage_name, target);
+        }
+    }
+
+    platforms::check_dependencies(&bake_file)?;
+
+    if config.cache.as_ref().is_some_and(|cache| cache.dependencies) {
+        bake_file.chain_caches();
+    }
+
+    // Add a default group with all targets, the root included
+    let mut targets: Vec<_> = std::iter::once(workspace.name.clone())
+        .chain(workspace.packages.keys().cloned())
+        .collect();
+    targets.sort();
+    bake_file.add_unique_group("default".to_string(), targets)?;
+
+    // Add a group per top-level directory, e.g. `apps` and `packages`. Like every other group,
+    // it can't take the name of a target or another group
+    let directory_groups = groups::directory_groups(
         workspace
             .packages
-            .clone()
-            .keys()
-            .map(|name| name.clone())
-            .collect(),
+            .iter()
+            .map(|(name, package)| (name.clone(), relative(&package.path).to_string_lossy().into_owned())),
     );
+    for (group, targets) in directory_groups {
+        bake_file
+            .add_unique_group(group.clone(), targets)
+            .with_context(|| format!("Can't add a group for the {} directory", group))?;
+    }
 
-    // Write the bake file to the workspace root
-    let bake_content = match args.format.as_str() {
-        "json" => serde_json::to_string_pretty(&bake_file)?,
-        "hcl" => bake_file.to_hcl(),
-        _ => return Err(anyhow::anyhow!("Unsupported format: {}", args.format)),
+    // Mirror every package target with one that only runs the Dockerfile build checks
+    if config.lint {
+        let targets: Vec<_> = std::iter::once(workspace.name.clone())
+            .chain(workspace.packages.keys().cloned())
+            .collect();
+        lint::add_lint(&mut bake_file, &targets)?;
+    }
+
+    // Fan packages out over the matrix axes, along with the root and dependencies they build on
+    for (name, matrix) in &config.matrix {
+        let mut packages: Vec<_> = workspace
+            .packages
+            .iter()
+            .filter(|(_, package)| config.in_matrix(matrix, &relative(&package.path)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        packages.sort();
+        matrix::add_matrix(&mut bake_file, name, matrix, &packages)?;
+    }
+
+    // Add a group per package tag, e.g. Nx's `scope:shop` becomes `scope-shop`
+    let mut tag_groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, package) in &workspace.packages {
+        for tag in &package.tags {
+            tag_groups
+                .entry(bake::sanitize_name(tag))
+                .or_default()
+                .push(name.clone());
+        }
+    }
+    for (group, mut targets) in tag_groups {
+        targets.sort();
+        bake_file
+            .add_unique_group(group.clone(), targets)
+            .with_context(|| format!("Can't add a group for the {} tag", group))?;
+    }
+
+    // Add a group with every package that produces a deployable image
+    let mut deployables: Vec<_> = workspace
+        .packages
+        .values()
+        .filter(|package| package.deployable)
+        .map(|package| package.name.clone())
+        .collect();
+    if !deployables.is_empty() {
+        deployables.sort();
+        bake_file.add_unique_group("deployables".to_string(), deployables.clone())?;
+    }
+
+    // Add an `<app>-all` group per deployable app with everything it's built from, so CI can
+    // build one product
+    for app in &deployables {
+        bake_file.add_unique_group(format!("{}-all", app), groups::closure(&workspace, app))?;
+    }
+
+    // Add the groups named in .bakehouse
+    for (group, group_config) in &config.groups {
+        let mut targets: Vec<_> = workspace
+            .packages
+            .iter()
+            .filter(|(_, package)| group_config.matches(&relative(&package.path), &package.tags))
+            .map(|(name, _)| name.clone())
+            .collect();
+        targets.sort();
+        bake_file
+            .add_unique_group(group.clone(), targets)
+            .with_context(|| format!("Can't add group {} from .bakehouse", group))?;
+    }
+
+    // Write the bake file to the workspace root, keeping any blocks added by hand when merging
+    let contents = match config.write_mode {
+        merge::WriteMode::Merge => {
+            if format != OutputFormat::Hcl {
+                anyhow::bail!("write_mode merge needs the HCL format, as JSON can't mark generated blocks");
+            }
+            let existing = match output_path.exists() {
+                true => std::fs::read_to_string(&output_path)?,
+                false => String::new(),
+            };
+            merge::merge(&existing, &bake_file)
+                .with_context(|| format!("Failed to merge into {}", output_path.display()))?
+        }
+        _ => bake_file.render(format)?,
     };
+    std::fs::write(&output_path, contents)?;
+
+    // Partitions get a file each, loaded after a common one with everything they share
+    if !config.partitions.is_empty() {
+        let mut assignment = BTreeMap::new();
+        for (name, package) in &workspace.packages {
+            let path = relative(&package.path);
+            let owners = code_owners.owners(&path);
+            if let Some((partition, _)) = config
+                .partitions
+                .iter()
+                .find(|(_, partition)| partition.matches(&path, &package.tags, owners))
+            {
+                assignment.insert(name.clone(), partition.clone());
+            }
+        }
 
-    std::fs::write(&output_path, bake_content)?;
+        let names: Vec<_> = config.partitions.keys().cloned().collect();
+        let (common, files) = partition::split(bake_file, &names, &assignment);
+        let partition_dir = workspace_root.join(config.partition_dir.as_deref().unwrap_or(std::path::Path::new("bake")));
+        std::fs::create_dir_all(&partition_dir)?;
+        std::fs::write(
+            partition_dir.join(format!("docker-bake.common.{}", format.extension())),
+            common.render(format)?,
+        )?;
+        for (name, file) in files {
+            let dir = partition_dir.join(&name);
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(dir.join(format!("docker-bake.{}", format.extension())), file.render(format)?)?;
+
+            // The override stub is for hand edits, so it's only written once
+            let (override_name, override_stub) = partition::override_stub(format);
+            let override_path = dir.join(override_name);
+            if !override_path.exists() {
+                std::fs::write(&override_path, override_stub)?;
+            }
+        }
+        println!("Generated partition bake files in: {}", partition_dir.display());
+    }
 
     println!("Generated Docker Bake file at: {}", output_path.display());
 
+    // cosign signs by digest, which is only known once bake has pushed the images
+    if let Some(sign) = config.attest.as_ref().and_then(|attest| attest.sign.as_ref()) {
+        let script_path = workspace_root.join(sign.script_path());
+        std::fs::write(&script_path, sign.script(&relative(&output_path).to_string_lossy()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        println!("Generated signing script at: {}", script_path.display());
+    }
+
     Ok(())
 }
@@ -0,0 +1,292 @@
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::bake::{sanitize_name, BakeFile, Target};
+
+/// Build arguments the built-in root templates read for well-known axes. Any other axis is
+/// passed as its name in upper case
+const AXIS_ARGS: &[(&str, &str)] = &[("node", "NODE_VERSION"), ("variant", "NODE_VARIANT")];
+
+/// A fan-out of the packages matching a glob over one or more axes, e.g. Node versions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MatrixConfig {
+    /// Glob of package paths, relative to the workspace root. Every package when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<String>,
+
+    /// Values for each axis. Every combination becomes a target
+    pub axes: IndexMap<String, Vec<String>>,
+}
+
+impl MatrixConfig {
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if sanitize_name(name) != name {
+            bail!("Invalid matrix name '{}'", name);
+        }
+        if let Some(pattern) = &self.packages {
+            glob::Pattern::new(pattern).with_context(|| format!("Invalid glob '{}' in matrix {}", pattern, name))?;
+        }
+        if self.axes.is_empty() {
+            bail!("Matrix {} has no axes", name);
+        }
+        for (axis, values) in &self.axes {
+            let is_identifier = axis.starts_with(|c: char| c.is_ascii_alphabetic())
+                && axis.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_identifier {
+                bail!("Invalid axis '{}' in matrix {}", axis, name);
+            }
+            if values.is_empty() {
+                bail!("Axis {} in matrix {} has no values", axis, name);
+            }
+            if let Some(value) = values.iter().find(|value| sanitize_name(&value.replace('.', "-")) != value.replace('.', "-")) {
+                bail!("Invalid value '{}' for axis {} in matrix {}", value, axis, name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The bake `name` suffix for one combination, as a template over the matrix variables.
+    /// Axes whose values are all numbers keep their name, so Node 20 is `node20` while a
+    /// variant is just `slim`
+    fn suffix_template(&self) -> String {
+        self.axes
+            .iter()
+            .map(|(axis, values)| {
+                let value = format!(r#"${{replace({}, ".", "-")}}"#, axis);
+                match numeric(values) {
+                    true => format!("{}{}", axis, value),
+                    false => value,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// The name parts of every combination, e.g. `["node20", "slim"]`
+    fn combinations(&self) -> Vec<Vec<String>> {
+        let mut combinations = vec![Vec::new()];
+        for (axis, values) in &self.axes {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|parts: Vec<String>| {
+                    values.iter().map(move |value| {
+                        let value = value.replace('.', "-");
+                        let mut parts = parts.clone();
+                        parts.push(match numeric(values) {
+                            true => format!("{}{}", axis, value),
+                            false => value,
+                        });
+                        parts
+                    })
+                })
+                .collect();
+        }
+
+        combinations
+    }
+
+    fn args(&self) -> BTreeMap<String, String> {
+        self.axes
+            .keys()
+            .map(|axis| {
+                let arg = AXIS_ARGS
+                    .iter()
+                    .find(|(name, _)| name == axis)
+                    .map(|(_, arg)| arg.to_string())
+                    .unwrap_or_else(|| axis.to_uppercase());
+                (arg, format!("${{{}}}", axis))
+            })
+            .collect()
+    }
+}
+
+fn numeric(values: &[String]) -> bool {
+    values
+        .iter()
+        .all(|value| value.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// The targets a target copies from through `target:` named contexts, its own or inherited
+fn target_contexts(bake_file: &BakeFile, name: &str) -> BTreeMap<String, String> {
+    bake_file
+        .contexts(name)
+        .into_iter()
+        .filter_map(|(context, source)| Some((context, source.strip_prefix("target:")?.to_string())))
+        .collect()
+}
+
+/// Add the matrix targets for `packages`, which inherit everything from their package's target
+/// but build with the combination's build arguments. Everything they copy from, the root and
+/// their dependencies included, gets a matrix target too, so nothing is built for another
+/// combination.
+///
+/// Targets are named `<target>-<matrix>-<suffix>`, e.g. `ui-test-node20`, and the packages'
+/// are grouped as `<matrix>` and `<matrix>-<value>` (`test-node20`) so a slice can be built on
+/// its own.
+pub fn add_matrix(bake_file: &mut BakeFile, name: &str, matrix: &MatrixConfig, packages: &[String]) -> Result<()> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+
+    let suffix = matrix.suffix_template();
+    let variant = |target: &str| format!("target:{}-{}-{}", target, name, suffix);
+
+    // The packages and every target they copy from, directly or not
+    let mut targets: Vec<String> = packages.to_vec();
+    let mut index = 0;
+    while let Some(target) = targets.get(index).cloned() {
+        for dep in target_contexts(bake_file, &target).into_values() {
+            if bake_file.target.contains_key(&dep) && !targets.contains(&dep) {
+                targets.push(dep);
+            }
+        }
+        index += 1;
+    }
+
+    for target in &targets {
+        let label = format!("{}-{}", target, name);
+        if bake_file.target.contains_key(&label) {
+            bail!("Matrix target {} has the same name as a target", label);
+        }
+        let tags = bake_file.target[target]
+            .tags
+            .iter()
+            .map(|tag| format!("{}-{}", tag, suffix))
+            .collect();
+        let contexts = target_contexts(bake_file, target)
+            .into_iter()
+            .filter(|(_, dep)| bake_file.target.contains_key(dep))
+            .map(|(context, dep)| (context, variant(&dep)))
+            .collect();
+
+        bake_file.add_target(
+            label.clone(),
+            Target {
+                name: Some(format!("{}-{}", label, suffix)),
+                matrix: matrix.axes.clone().into_iter().collect(),
+                inherits: vec![target.clone()],
+                tags,
+                contexts,
+                args: matrix.args(),
+                ..Default::default()
+            },
+        );
+    }
+
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for package in packages {
+        let label = format!("{}-{}", package, name);
+        for parts in matrix.combinations() {
+            let target = format!("{}-{}", label, parts.join("-"));
+            groups.entry(name.to_string()).or_default().push(target.clone());
+            for part in parts {
+                groups.entry(format!("{}-{}", name, part)).or_default().push(target.clone());
+            }
+        }
+    }
+
+    for (group, mut targets) in groups {
+        targets.sort();
+        bake_file
+            .add_unique_group(group.clone(), targets)
+            .with_context(|| format!("Can't add the groups of matrix {}", name))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn matrix() -> MatrixConfig {
+        serde_yaml::from_str(
+            r#"
+packages: "packages/*"
+axes:
+  node: ["18", "20.11"]
+  variant: [alpine, slim]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(matrix().validate("test").is_ok());
+        assert!(matrix().validate("not valid").is_err());
+
+        let mut bad_value = matrix();
+        bad_value.axes["variant"].push("a/b".to_string());
+        assert!(bad_value.validate("test").is_err());
+    }
+
+    #[test]
+    fn test_add_matrix() -> Result<()> {
+        let mut bake_file = BakeFile::new();
+        for (name, deps) in [("mono", vec![]), ("logger", vec!["mono"]), ("ui", vec!["mono", "logger"])] {
+            bake_file.add_target(
+                name.to_string(),
+                Target::new(
+                    Path::new("."),
+                    Path::new("."),
+                    "Dockerfile.bake".to_string(),
+                    vec![format!("{}:1.0.0", name)],
+                    deps.into_iter().map(|dep| (dep.to_string(), format!("target:{}", dep))).collect(),
+                ),
+            );
+        }
+
+        add_matrix(&mut bake_file, "test", &matrix(), &["ui".to_string()])?;
+
+        let hcl = bake_file.to_hcl()?;
+        assert!(hcl.contains(
+            r#"target "ui-test" {
+  name = "ui-test-node${replace(node, ".", "-")}-${replace(variant, ".", "-")}"
+  matrix = {
+    node = ["18", "20.11"]
+    variant = ["alpine", "slim"]
+  }
+  inherits = ["ui"]
+  tags = ["ui:1.0.0-node${replace(node, ".", "-")}-${replace(variant, ".", "-")}"]
+  contexts = {
+    "logger" = "target:logger-test-node${replace(node, ".", "-")}-${replace(variant, ".", "-")}"
+    "mono" = "target:mono-test-node${replace(node, ".", "-")}-${replace(variant, ".", "-")}"
+  }
+  args = {
+    "NODE_VARIANT" = "${variant}"
+    "NODE_VERSION" = "${node}"
+  }
+}"#
+        ));
+        assert!(hcl.contains("target \"mono-test\" {\n  name = \"mono-test-node"));
+
+        // A dependency is built for the same combination as its dependent
+        let logger = &bake_file.target["logger-test"];
+        assert_eq!(logger.inherits, ["logger"]);
+        assert_eq!(
+            logger.contexts["mono"],
+            r#"target:mono-test-node${replace(node, ".", "-")}-${replace(variant, ".", "-")}"#
+        );
+
+        assert_eq!(
+            bake_file.group["test-node20-11"].targets,
+            vec!["ui-test-node20-11-alpine", "ui-test-node20-11-slim"]
+        );
+        assert_eq!(bake_file.group["test-slim"].targets, vec!["ui-test-node18-slim", "ui-test-node20-11-slim"]);
+        assert_eq!(bake_file.group["test"].targets.len(), 4);
+
+        // Neither a target nor a group is replaced
+        assert!(add_matrix(&mut bake_file, "test", &matrix(), &["ui".to_string()]).is_err());
+        let mut bake_file = BakeFile::new();
+        bake_file.add_target("ui".to_string(), Target::default());
+        bake_file.add_group("lint".to_string(), vec!["ui".to_string()]);
+        assert!(add_matrix(&mut bake_file, "lint", &matrix(), &["ui".to_string()]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, bail, Context, Result};
+use hcl_edit::Decorate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::bake::{BakeFile, OutputFormat};
+
+/// Comment marking the blocks bakehouse owns in a merged bake file
+const GENERATED_MARKER: &str = "# Generated by bakehouse";
+
+/// What happens to an existing bake file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteMode {
+    /// Replace the whole file
+    #[default]
+    Overwrite,
+    /// Replace only the blocks marked as generated, keeping everything added by hand
+    Merge,
+    /// Write `docker-bake.generated.<ext>`, to be layered after a hand-maintained file
+    Separate,
+}
+
+impl WriteMode {
+    /// Bake file name when `--output` isn't given
+    pub fn default_file_name(&self, format: OutputFormat) -> String {
+        match self {
+            WriteMode::Separate => format!("docker-bake.generated.{}", format.extension()),
+            _ => format!("docker-bake.{}", format.extension()),
+        }
+    }
+}
+
+/// Merge the generated blocks into an existing HCL bake file. Blocks marked as generated by
+/// an earlier run are replaced, while every other block keeps its place, formatting and
+/// comments. A hand-written block with the same name as a generated one is an error, as
+/// bake would silently merge the two
+pub fn merge(existing: &str, bake_file: &BakeFile) -> Result<String> {
+    let body = hcl_edit::parser::parse_body(existing)
+        .map_err(|error| anyhow!("{}", error))
+        .context("Failed to parse the existing bake file")?;
+
+    let generated: HashSet<(&str, &str)> = bake_file
+        .variable
+        .keys()
+        .map(|name| ("variable", name.as_str()))
+        .chain(bake_file.group.keys().map(|name| ("group", name.as_str())))
+        .chain(bake_file.target.keys().map(|name| ("target", name.as_str())))
+        .collect();
+
+    let mut kept = Vec::new();
+    for structure in body {
+        if let Some(block) = structure.as_block() {
+            let is_generated = block
+                .decor()
+                .prefix()
+                .is_some_and(|prefix| prefix.contains(GENERATED_MARKER));
+            if is_generated {
+                continue;
+            }
+            if let Some(label) = block.labels.first() {
+                if generated.contains(&(block.ident.as_str(), label.as_str())) {
+                    bail!(
+                        "{} \"{}\" is written by hand in the bake file, but bakehouse generates it too",
+                        block.ident.as_str(),
+                        label.as_str()
+                    );
+                }
+            }
+        }
+        kept.push(structure);
+    }
+
+    let hand_written = hcl_edit::structure::Body::from(kept).to_string();
+    let mut merged = hand_written.trim().to_string();
+    if !merged.is_empty() {
+        merged.push_str("\n\n");
+    }
+    merged.push_str(&mark_blocks(&bake_file.to_hcl()?));
+
+    Ok(merged)
+}
+
+/// Put the marker before every top-level block of formatted HCL
+fn mark_blocks(hcl: &str) -> String {
+    hcl.lines()
+        .map(|line| match line.starts_with(|c: char| !c.is_whitespace() && c != '}') {
+            true => format!("{}\n{}\n", GENERATED_MARKER, line),
+            false => format!("{}\n", line),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bake::Target;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    fn bake_file(targets: &[&str]) -> BakeFile {
+        let mut bake_file = BakeFile::new();
+        bake_file.add_variable("TAG".to_string(), String::new());
+        for name in targets {
+            bake_file.add_target(
+                name.to_string(),
+                Target::new(Path::new("."), Path::new("."), "Dockerfile".to_string(), vec![], BTreeMap::new()),
+            );
+        }
+        bake_file
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let first = merge("", &bake_file(&["api", "web"]))?;
+        assert!(first.starts_with("# Generated by bakehouse\nvariable \"TAG\" {\n"));
+        assert!(first.contains("# Generated by bakehouse\ntarget \"web\" {\n"));
+
+        // Blocks added by hand survive, comments and all, while generated ones are replaced
+        let edited = format!(
+            "# Integration tests\ntarget \"e2e\" {{\n  contexts = {{ api = \"target:api\" }} # needs the API\n}}\n\n{}",
+            first
+        );
+        let second = merge(&edited, &bake_file(&["api"]))?;
+        assert!(second.starts_with(
+            "# Integration tests\ntarget \"e2e\" {\n  contexts = { api = \"target:api\" } # needs the API\n}\n\n"
+        ));
+        assert!(second.contains("target \"api\""));
+        assert!(!second.contains("target \"web\""));
+        assert_eq!(second.matches("target \"api\"").count(), 1);
+        assert_eq!(merge(&second, &bake_file(&["api"]))?, second);
+
+        // A hand-written block can't share a generated block's name
+        let error = merge("target \"api\" {}\n", &bake_file(&["api"])).unwrap_err();
+        assert!(error.to_string().starts_with("target \"api\" is written by hand"));
+
+        assert!(merge("target \"api\" {", &bake_file(&["api"])).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bake::render_pattern;
+
+/// Stage a target builds when all it exports is files, rather than an image
+pub const EXPORT_STAGE: &str = "export";
+
+/// Bake exporters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputKind {
+    /// The files of the built stage, written to a directory
+    Local,
+    /// The same files as a tarball
+    Tar,
+    /// The image as an OCI layout tarball, e.g. for air-gapped registries
+    Oci,
+    /// The image as a Docker tarball, or loaded into the local image store without a `dest`
+    Docker,
+    /// The image, pushed with `--push`
+    Image,
+}
+
+impl OutputKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputKind::Local => "local",
+            OutputKind::Tar => "tar",
+            OutputKind::Oci => "oci",
+            OutputKind::Docker => "docker",
+            OutputKind::Image => "image",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputConfig {
+    #[serde(rename = "type")]
+    pub kind: OutputKind,
+
+    /// Where to write the files or tarball, relative to where bake runs. `${name}` is the
+    /// target name, anything else is interpolated by bake. Defaults to `out/<name>`, plus
+    /// `.tar` for tarballs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dest: Option<String>,
+}
+
+impl OutputConfig {
+    /// The `output` entry for a target
+    pub fn entry(&self, name: &str) -> String {
+        let dest = match (&self.dest, self.kind) {
+            (Some(dest), _) => Some(render_pattern(dest, name, "")),
+            (None, OutputKind::Local) => Some(format!("out/{}", name)),
+            (None, OutputKind::Tar | OutputKind::Oci) => Some(format!("out/{}.tar", name)),
+            (None, OutputKind::Docker | OutputKind::Image) => None,
+        };
+        match dest {
+            Some(dest) => format!("type={},dest={}", self.kind.name(), dest),
+            None => format!("type={}", self.kind.name()),
+        }
+    }
+
+    /// Whether the output is files from the build rather than an image
+    pub fn is_export(&self) -> bool {
+        matches!(self.kind, OutputKind::Local | OutputKind::Tar)
+    }
+}
+
+/// Whether a Dockerfile has a stage named `stage`
+pub fn has_stage(dockerfile: &str, stage: &str) -> bool {
+    dockerfile.lines().any(|line| {
+        let words: Vec<_> = line.split_whitespace().collect();
+        words.first().is_some_and(|word| word.eq_ignore_ascii_case("FROM"))
+            && words
+                .windows(2)
+                .any(|pair| pair[0].eq_ignore_ascii_case("AS") && pair[1].eq_ignore_ascii_case(stage))
+    })
+}
+
+/// A dependent copies from a dependency's final image, which a target building only the
+/// export stage doesn't give it, unless the dependency names another stage to copy from
+pub fn check_dependencies(name: &str, contexts: &BTreeMap<String, String>, export_only: &BTreeSet<String>) -> Result<()> {
+    for (dep, context) in contexts {
+        if export_only.contains(dep) && *context == format!("target:{}", dep) {
+            bail!(
+                "{} depends on {}, which only exports files, so there's no image to copy from. Give {} an image output or a `dependency_stage`",
+                name,
+                dep,
+                dep
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> OutputConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_entry() {
+        assert_eq!(config("type: local").entry("web"), "type=local,dest=out/web");
+        assert_eq!(config("type: oci").entry("web"), "type=oci,dest=out/web.tar");
+        assert_eq!(
+            config("type: tar\ndest: dist/${name}-${TAG}.tar").entry("cli"),
+            "type=tar,dest=dist/cli-${TAG}.tar"
+        );
+        assert_eq!(config("type: image").entry("web"), "type=image");
+
+        assert!(config("type: local").is_export());
+        assert!(!config("type: oci").is_export());
+    }
+
+    #[test]
+    fn test_has_stage() {
+        let dockerfile = "FROM node:20 AS build\nRUN make\n\nfrom scratch as Export\nCOPY --from=build /out /\n";
+        assert!(has_stage(dockerfile, EXPORT_STAGE));
+        assert!(has_stage(dockerfile, "build"));
+        assert!(!has_stage("FROM node:20\n# AS export\n", EXPORT_STAGE));
+    }
+
+    #[test]
+    fn test_check_dependencies() {
+        let export_only = BTreeSet::from(["cli".to_string()]);
+        let contexts = BTreeMap::from([("cli".to_string(), "target:cli".to_string())]);
+        assert!(check_dependencies("installer", &contexts, &export_only).is_err());
+
+        let contexts = BTreeMap::from([("cli".to_string(), "target:cli-build".to_string())]);
+        assert!(check_dependencies("installer", &contexts, &export_only).is_ok());
+        assert!(check_dependencies("installer", &contexts, &BTreeSet::new()).is_ok());
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::bake::{sanitize_name, BakeFile, OutputFormat, Target};
+
+/// Stub written next to each partition's HCL bake file, and never overwritten
+const OVERRIDE_STUB: &str = "\
+# Overrides for this partition's targets, kept across bakehouse runs. Load it last:
+#
+#   docker buildx bake -f <dir>/docker-bake.common.hcl -f <dir>/<partition>/docker-bake.hcl \\
+#     -f <dir>/<partition>/docker-bake.override.hcl
+#
+# target \"my-app\" {
+#   args = { NODE_ENV = \"staging\" }
+# }
+";
+
+/// The override stub for a format, as `docker-bake.override.<ext>`. JSON has no comments, so
+/// its stub is just an empty file for bake
+pub fn override_stub(format: OutputFormat) -> (String, &'static str) {
+    let contents = match format {
+        OutputFormat::Hcl => OVERRIDE_STUB,
+        OutputFormat::Json => "{}\n",
+    };
+    (format!("docker-bake.override.{}", format.extension()), contents)
+}
+
+/// A slice of the workspace with its own bake file, selected by path glob, tag or owner
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartitionConfig {
+    /// Glob of package paths, relative to the workspace root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<String>,
+
+    /// Package tags, e.g. Nx's `scope:shop`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// CODEOWNERS owners, e.g. `@acme/shop`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+}
+
+impl PartitionConfig {
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if sanitize_name(name) != name {
+            bail!("Invalid partition name '{}'", name);
+        }
+        if self.packages.is_none() && self.tags.is_empty() && self.owners.is_empty() {
+            bail!("Partition {} selects no packages, set `packages`, `tags` or `owners`", name);
+        }
+        if let Some(pattern) = &self.packages {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid glob '{}' in partition {}", pattern, name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a package, by its path relative to the workspace root, its tags and its
+    /// owners, belongs to the partition
+    pub fn matches(&self, package_path: &Path, tags: &[String], owners: &[String]) -> bool {
+        let by_path = self
+            .packages
+            .as_ref()
+            .is_some_and(|pattern| glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches_path(package_path)));
+        by_path
+            || tags.iter().any(|tag| self.tags.contains(tag))
+            || owners.iter().any(|owner| self.owners.contains(owner))
+    }
+}
+
+/// Split a bake file into a common file and one per partition. `assignment` maps package
+/// targets to their partition.
+///
+/// Every partition's file is loaded together with the common one, so a target stays in its
+/// partition only when nothing outside it depends on it. Everything else, the root, bases
+/// and variables included, goes into the common file. Groups go wherever all their targets
+/// can be found, and are left out when they span partitions; each partition also gets a
+/// `default` group of the targets it builds.
+pub fn split(
+    bake_file: BakeFile,
+    partitions: &[String],
+    assignment: &BTreeMap<String, String>,
+) -> (BakeFile, BTreeMap<String, BakeFile>) {
+    // Matrix, lint and stage targets go with the package they inherit from
+    let mut location: BTreeMap<String, Option<String>> = bake_file
+        .target
+        .iter()
+        .map(|(name, target)| {
+            let partition = assignment
+                .get(name)
+                .or_else(|| target.inherits.first().and_then(|package| assignment.get(package)));
+            (name.clone(), partition.cloned())
+        })
+        .collect();
+    loop {
+        let mut shared = Vec::new();
+        for (name, target) in &bake_file.target {
+            for dependency in dependencies(target) {
+                if matches!(location.get(dependency), Some(Some(_))) && location[dependency] != location[name] {
+                    shared.push(dependency.to_string());
+                }
+            }
+        }
+        if shared.is_empty() {
+            break;
+        }
+        for name in shared {
+            location.insert(name, None);
+        }
+    }
+
+    let mut group_locations = BTreeMap::new();
+    for (name, group) in &bake_file.group {
+        let mut partition = None;
+        let mut placeable = true;
+        for target in &group.targets {
+            match resolve(&bake_file, target).map(|target| &location[target]) {
+                None => placeable = false,
+                Some(None) => {}
+                Some(Some(found)) => match partition {
+                    None => partition = Some(found),
+                    Some(partition) if partition == found => {}
+                    Some(_) => placeable = false,
+                },
+            }
+        }
+        if placeable {
+            group_locations.insert(name.clone(), partition.cloned());
+        }
+    }
+
+    let mut common = BakeFile::new();
+    let mut files: BTreeMap<String, BakeFile> = partitions.iter().map(|name| (name.clone(), BakeFile::new())).collect();
+    common.variable = bake_file.variable;
+    for (name, target) in bake_file.target {
+        match &location[&name] {
+            Some(partition) => files.get_mut(partition).unwrap_or(&mut common).target.insert(name, target),
+            None => common.target.insert(name, target),
+        };
+    }
+    for (name, group) in bake_file.group {
+        match group_locations.get(&name) {
+            Some(Some(partition)) => files.get_mut(partition).unwrap_or(&mut common).group.insert(name, group),
+            Some(None) => common.group.insert(name, group),
+            None => None,
+        };
+    }
+    for file in files.values_mut() {
+        let targets: Vec<_> = file
+            .target
+            .iter()
+            .filter(|(_, target)| target.call.is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+        file.add_group("default".to_string(), targets);
+    }
+
+    (common, files)
+}
+
+/// Targets a target needs to be loaded alongside it. Matrix targets depend on expanded
+/// names, which only the root's matrix targets have and they're always common
+fn dependencies(target: &Target) -> impl Iterator<Item = &str> {
+    target
+        .inherits
+        .iter()
+        .map(String::as_str)
+        .chain(target.contexts.values().filter_map(|context| context.strip_prefix("target:")))
+        .filter(|name| !name.contains("${"))
+}
+
+/// The target a group entry refers to, which for a matrix is one of its expanded names
+fn resolve<'a>(bake_file: &'a BakeFile, name: &str) -> Option<&'a str> {
+    if let Some((name, _)) = bake_file.target.get_key_value(name) {
+        return Some(name);
+    }
+    bake_file
+        .target
+        .iter()
+        .filter(|(target_name, target)| {
+            !target.matrix.is_empty() && name.starts_with(&format!("{}-", target_name))
+        })
+        .map(|(target_name, _)| target_name.as_str())
+        .max_by_key(|target_name| target_name.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(dependencies: &[&str]) -> Target {
+        Target {
+            contexts: dependencies
+                .iter()
+                .map(|dep| (dep.to_string(), format!("target:{}", dep)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let partition: PartitionConfig = serde_yaml::from_str("owners: [\"@acme/shop\"]").unwrap();
+        assert!(partition.validate("shop").is_ok());
+        assert!(PartitionConfig::default().validate("shop").is_err());
+        assert!(partition.matches(Path::new("apps/cart"), &[], &["@acme/shop".to_string()]));
+        assert!(!partition.matches(Path::new("apps/cart"), &[], &["@acme/web".to_string()]));
+    }
+
+    #[test]
+    fn test_split() {
+        let mut bake_file = BakeFile::new();
+        bake_file.add_variable("TAG".to_string(), String::new());
+        bake_file.add_target("root".to_string(), target(&[]));
+        bake_file.add_target("ui".to_string(), target(&["root"]));
+        bake_file.add_target("cart".to_string(), target(&["root", "ui"]));
+        bake_file.add_target("shop".to_string(), target(&["root", "cart"]));
+        bake_file.add_target("admin".to_string(), target(&["root", "ui"]));
+        bake_file.add_target(
+            "shop-test".to_string(),
+            Target {
+                matrix: BTreeMap::from([("node".to_string(), vec!["20".to_string()])]),
+                inherits: vec!["shop".to_string()],
+                ..Default::default()
+            },
+        );
+        bake_file.add_target(
+            "shop-lint".to_string(),
+            Target {
+                inherits: vec!["shop".to_string()],
+                call: Some("check".to_string()),
+                ..Default::default()
+            },
+        );
+        bake_file.add_group("shop-all".to_string(), vec!["cart".into(), "root".into(), "shop".into(), "ui".into()]);
+        bake_file.add_group("test".to_string(), vec!["shop-test-node20".into()]);
+        bake_file.add_group("default".to_string(), vec!["admin".into(), "shop".into()]);
+
+        let assignment = BTreeMap::from([
+            ("ui".to_string(), "admin".to_string()),
+            ("cart".to_string(), "shop".to_string()),
+            ("shop".to_string(), "shop".to_string()),
+            ("admin".to_string(), "admin".to_string()),
+        ]);
+        let (common, files) = split(bake_file, &["admin".to_string(), "shop".to_string()], &assignment);
+
+        // `ui` is used by both partitions, so it's shared
+        assert_eq!(common.target.keys().collect::<Vec<_>>(), ["root", "ui"]);
+        assert!(common.variable.contains_key("TAG"));
+        assert!(common.group.is_empty());
+
+        let shop = &files["shop"];
+        assert_eq!(shop.target.keys().collect::<Vec<_>>(), ["cart", "shop", "shop-lint", "shop-test"]);
+        assert_eq!(shop.group.keys().collect::<Vec<_>>(), ["default", "shop-all", "test"]);
+        assert_eq!(shop.group["default"].targets, ["cart", "shop", "shop-test"]);
+
+        assert_eq!(files["admin"].target.keys().collect::<Vec<_>>(), ["admin"]);
+    }
+
+    #[test]
+    fn test_override_stub() {
+        let (name, contents) = override_stub(OutputFormat::Hcl);
+        assert_eq!(name, "docker-bake.override.hcl");
+        assert!(contents.starts_with("# Overrides"));
+
+        let (name, contents) = override_stub(OutputFormat::Json);
+        assert_eq!(name, "docker-bake.override.json");
+        assert!(serde_json::from_str::<serde_json::Value>(contents).is_ok());
+    }
+}
@@ -0,0 +1,330 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use tera::{Tera, Value};
+
+use crate::bake::{BakeFile, Target};
+
+/// Platforms the official images used by the built-in templates are published for. The
+/// first pattern matching the image wins; other images aren't checked.
+const KNOWN_IMAGES: &[(&str, &[&str])] = &[
+    (
+        "node:*alpine*",
+        &["linux/amd64", "linux/arm/v6", "linux/arm/v7", "linux/arm64", "linux/ppc64le", "linux/s390x"],
+    ),
+    (
+        "node:*",
+        &["linux/amd64", "linux/arm/v7", "linux/arm64", "linux/ppc64le", "linux/s390x"],
+    ),
+    (
+        "rust:*",
+        &["linux/386", "linux/amd64", "linux/arm/v7", "linux/arm64", "linux/ppc64le", "linux/s390x"],
+    ),
+    (
+        "golang:*alpine*",
+        &[
+            "linux/386",
+            "linux/amd64",
+            "linux/arm/v6",
+            "linux/arm/v7",
+            "linux/arm64",
+            "linux/ppc64le",
+            "linux/riscv64",
+            "linux/s390x",
+        ],
+    ),
+    (
+        "golang:*",
+        &[
+            "linux/386",
+            "linux/amd64",
+            "linux/arm/v5",
+            "linux/arm/v7",
+            "linux/arm64",
+            "linux/mips64le",
+            "linux/ppc64le",
+            "linux/s390x",
+        ],
+    ),
+];
+
+/// A platform split into the parts BuildKit exposes as `TARGETOS`, `TARGETARCH` and `TARGETVARIANT`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Platform {
+    pub platform: String,
+    pub os: String,
+    pub arch: String,
+    pub variant: String,
+}
+
+impl Platform {
+    pub fn parse(platform: &str) -> Result<Self> {
+        let parts: Vec<_> = platform.split('/').collect();
+        let (os, arch, variant) = match parts.as_slice() {
+            [os, arch] => (*os, *arch, ""),
+            [os, arch, variant] => (*os, *arch, *variant),
+            _ => bail!("Invalid platform '{}', expected os/arch[/variant]", platform),
+        };
+        if os.is_empty() || arch.is_empty() {
+            bail!("Invalid platform '{}', expected os/arch[/variant]", platform);
+        }
+
+        Ok(Self {
+            platform: platform.to_string(),
+            os: os.to_string(),
+            arch: arch.to_string(),
+            variant: variant.to_string(),
+        })
+    }
+
+    /// The name registries use for this platform: `linux/arm64/v8` is `linux/arm64` and a bare
+    /// `linux/arm` is `linux/arm/v7`
+    fn normalized(&self) -> String {
+        let variant = match (self.arch.as_str(), self.variant.as_str()) {
+            ("arm64", "v8") => "",
+            ("arm", "") => "v7",
+            (_, variant) => variant,
+        };
+
+        if variant.is_empty() {
+            format!("{}/{}", self.os, self.arch)
+        } else {
+            format!("{}/{}/{}", self.os, self.arch, variant)
+        }
+    }
+}
+
+/// Parse a target's platforms for its template context
+pub fn parse_all(platforms: &[String]) -> Result<Vec<Platform>> {
+    platforms.iter().map(|platform| Platform::parse(platform)).collect()
+}
+
+/// Check a target's platforms against those its Dockerfile's base image is published for
+pub fn check_base_image(target: &str, platforms: &[String], dockerfile: &str) -> Result<()> {
+    let Some(image) = base_image(dockerfile) else {
+        return Ok(());
+    };
+    let Some((_, supported)) = KNOWN_IMAGES
+        .iter()
+        .find(|(pattern, _)| glob::Pattern::new(pattern).is_ok_and(|glob| glob.matches(&image)))
+    else {
+        return Ok(());
+    };
+
+    for platform in parse_all(platforms)? {
+        if !supported.contains(&platform.normalized().as_str()) {
+            bail!(
+                "{} can't be built for {}: {} is only published for {}",
+                target,
+                platform.platform,
+                image,
+                supported.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that every target's dependencies are built for at least the target's platforms,
+/// as a `target:` context has to exist for each platform that uses it
+pub fn check_dependencies(bake_file: &BakeFile) -> Result<()> {
+    for (name, target) in &bake_file.target {
+        let dependencies = target
+            .contexts
+            .values()
+            .filter_map(|context| context.strip_prefix("target:"));
+        for dependency in dependencies {
+            let Some(dependency_target) = bake_file.target.get(dependency) else {
+                continue;
+            };
+            let dependency_platforms = target_platforms(bake_file, dependency_target);
+            if target.platforms.is_empty() || dependency_platforms.is_empty() {
+                continue;
+            }
+            let missing: Vec<_> = target
+                .platforms
+                .iter()
+                .filter(|platform| !dependency_platforms.contains(platform))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                bail!(
+                    "{} is built for {}, but its dependency {} isn't",
+                    name,
+                    missing.join(", "),
+                    dependency
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A target's platforms, or those it inherits, e.g. a stage target from its package
+fn target_platforms<'a>(bake_file: &'a BakeFile, target: &'a Target) -> &'a [String] {
+    if !target.platforms.is_empty() {
+        return &target.platforms;
+    }
+    target
+        .inherits
+        .iter()
+        .rev()
+        .filter_map(|name| bake_file.target.get(name))
+        .map(|target| target_platforms(bake_file, target))
+        .find(|platforms| !platforms.is_empty())
+        .unwrap_or_default()
+}
+
+/// The image of a Dockerfile's first stage, with build arguments declared before it
+/// replaced by their defaults. `None` when it depends on an argument without one
+fn base_image(dockerfile: &str) -> Option<String> {
+    let mut args = HashMap::new();
+    for line in dockerfile.lines().map(str::trim) {
+        let Some((instruction, rest)) = line.split_once(' ') else {
+            continue;
+        };
+        if instruction.eq_ignore_ascii_case("ARG") {
+            if let Some((name, default)) = rest.trim().split_once('=') {
+                args.insert(name.to_string(), default.trim_matches('"').to_string());
+            }
+        } else if instruction.eq_ignore_ascii_case("FROM") {
+            let mut image = rest.split_whitespace().find(|word| !word.starts_with("--"))?.to_string();
+            for (name, default) in &args {
+                image = image
+                    .replace(&format!("${{{}}}", name), default)
+                    .replace(&format!("${}", name), default);
+            }
+            return (!image.contains('$')).then_some(image);
+        }
+    }
+
+    None
+}
+
+/// Register the helpers templates use to install native binaries for `TARGETPLATFORM`:
+///
+/// - `platform_args()` declares the `TARGET*` build arguments in the current stage
+/// - `platform_arch(amd64="x86_64", arm64="aarch64")` expands to a shell expression printing
+///   the name a download uses for `TARGETARCH`, falling back to `TARGETARCH` itself
+pub fn register(tera: &mut Tera) {
+    tera.register_function("platform_args", |_: &HashMap<String, Value>| {
+        Ok(Value::String(
+            ["TARGETPLATFORM", "TARGETOS", "TARGETARCH", "TARGETVARIANT"]
+                .map(|arg| format!("ARG {}", arg))
+                .join("\n"),
+        ))
+    });
+
+    tera.register_function("platform_arch", |args: &HashMap<String, Value>| {
+        let mut names: Vec<_> = args.iter().collect();
+        names.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut cases = String::new();
+        for (arch, name) in names {
+            let name = name
+                .as_str()
+                .ok_or_else(|| tera::Error::msg(format!("platform_arch: {} must be a string", arch)))?;
+            cases.push_str(&format!("{}) echo {} ;; ", arch, name));
+        }
+
+        Ok(Value::String(format!(
+            r#"$(case "${{TARGETARCH}}" in {}*) echo "${{TARGETARCH}}" ;; esac)"#,
+            cases
+        )))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bake::Target;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    #[test]
+    fn test_check_base_image() -> Result<()> {
+        let dockerfile = "# syntax=docker/dockerfile:1\nFROM --platform=$BUILDPLATFORM node:20-alpine AS build\n";
+        let platforms = |platforms: &[&str]| platforms.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        check_base_image("web", &platforms(&["linux/amd64", "linux/arm64/v8", "linux/arm"]), dockerfile)?;
+
+        let error = check_base_image("web", &platforms(&["linux/386"]), dockerfile).unwrap_err();
+        assert!(error.to_string().starts_with("web can't be built for linux/386: node:20-alpine"));
+
+        assert!(check_base_image("web", &platforms(&["arm64"]), dockerfile).is_err());
+
+        // Images bakehouse doesn't know about, or that depend on build arguments, aren't checked
+        check_base_image("web", &platforms(&["linux/riscv64"]), "FROM sample-monorepo\n")?;
+        check_base_image("web", &platforms(&["linux/riscv64"]), "ARG IMAGE\nFROM ${IMAGE}\n")?;
+
+        // Build argument defaults are filled in
+        let dockerfile = "ARG NODE_VERSION=20\nARG NODE_VARIANT=alpine\nFROM node:${NODE_VERSION}-$NODE_VARIANT\n";
+        assert!(check_base_image("web", &platforms(&["linux/386"]), dockerfile).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_dependencies() {
+        let target = |platforms: &[&str], dependency: Option<&str>| {
+            let mut target = Target::new(
+                Path::new("."),
+                Path::new("."),
+                "Dockerfile".to_string(),
+                vec![],
+                dependency
+                    .map(|dep| BTreeMap::from([(dep.to_string(), format!("target:{}", dep))]))
+                    .unwrap_or_default(),
+            );
+            target.platforms = platforms.iter().map(|p| p.to_string()).collect();
+            target
+        };
+
+        let mut bake_file = BakeFile::new();
+        bake_file.add_target("root".to_string(), target(&["linux/amd64", "linux/arm64"], None));
+        bake_file.add_target("api".to_string(), target(&["linux/arm64"], Some("root")));
+        assert!(check_dependencies(&bake_file).is_ok());
+
+        bake_file.add_target("lib".to_string(), target(&["linux/amd64"], Some("root")));
+        bake_file.add_target("app".to_string(), target(&["linux/amd64", "linux/arm64"], Some("lib")));
+        assert_eq!(
+            check_dependencies(&bake_file).unwrap_err().to_string(),
+            "app is built for linux/arm64, but its dependency lib isn't"
+        );
+
+        // A stage target is built for its package's platforms
+        let mut bake_file = BakeFile::new();
+        bake_file.add_target("lib".to_string(), target(&["linux/amd64"], None));
+        bake_file.add_target(
+            "lib-build".to_string(),
+            Target {
+                inherits: vec!["lib".to_string()],
+                target: Some("build".to_string()),
+                ..Default::default()
+            },
+        );
+        bake_file.add_target("app".to_string(), target(&["linux/arm64"], Some("lib-build")));
+        assert!(check_dependencies(&bake_file).is_err());
+    }
+
+    #[test]
+    fn test_helpers() -> Result<()> {
+        let mut tera = Tera::default();
+        register(&mut tera);
+        tera.add_raw_template(
+            "dockerfile",
+            "{{ platform_args() }}\nRUN curl -fsSL https://example.com/tool-{{ platform_arch(amd64=\"x86_64\", arm64=\"aarch64\") }}.tgz",
+        )?;
+
+        assert_eq!(
+            tera.render("dockerfile", &tera::Context::new())?,
+            "ARG TARGETPLATFORM\nARG TARGETOS\nARG TARGETARCH\nARG TARGETVARIANT\n\
+             RUN curl -fsSL https://example.com/tool-$(case \"${TARGETARCH}\" in \
+             amd64) echo x86_64 ;; arm64) echo aarch64 ;; *) echo \"${TARGETARCH}\" ;; esac).tgz"
+        );
+
+        Ok(())
+    }
+}
@@ -1 +1,408 @@
+pub mod cargo;
+pub mod external;
+pub mod go;
+pub mod nx;
 pub mod pnpm;
+pub mod rush;
+#[cfg(test)]
+mod test_support;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{BakehouseConfig, ResolverConfig};
+use crate::templates;
+use crate::workspace::Workspace;
+
+/// The ecosystems bakehouse knows how to resolve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolverKind {
+    Pnpm,
+    Nx,
+    Rush,
+    Cargo,
+    Go,
+    /// An executable named in `.bakehouse`, see [`external`]
+    External,
+}
+
+impl ResolverKind {
+    /// All detectable resolvers, in the order they are detected and merged
+    pub const ALL: [ResolverKind; 5] = [
+        ResolverKind::Pnpm,
+        ResolverKind::Nx,
+        ResolverKind::Rush,
+        ResolverKind::Cargo,
+        ResolverKind::Go,
+    ];
+
+    /// Lockfiles and manifests that mark a directory as belonging to this ecosystem
+    fn markers(&self) -> &'static [&'static str] {
+        match self {
+            ResolverKind::Pnpm => &["pnpm-workspace.yaml", "pnpm-lock.yaml"],
+            ResolverKind::Nx => &["nx.json"],
+            ResolverKind::Rush => &["rush.json"],
+            ResolverKind::Cargo => &["Cargo.toml"],
+            ResolverKind::Go => &["go.work", "go.mod"],
+            // Only ever run when configured
+            ResolverKind::External => &[],
+        }
+    }
+
+    /// Resolvers this one replaces when both are found in the same directory. An Nx
+    /// workspace is usually a pnpm workspace too, but Nx knows more about its projects.
+    fn supersedes(&self) -> &'static [ResolverKind] {
+        match self {
+            ResolverKind::Nx => &[ResolverKind::Pnpm],
+            _ => &[],
+        }
+    }
+
+    fn detect(&self, dir: &Path) -> bool {
+        self.markers().iter().any(|marker| dir.join(marker).is_file())
+    }
+
+    fn load(&self, root: &Path, resolver: &ResolverConfig, config: &BakehouseConfig) -> Result<Workspace> {
+        Ok(match self {
+            ResolverKind::Pnpm => Workspace::new(pnpm::load_workspace(root, config.typescript_references)?),
+            ResolverKind::Nx => Workspace::new(nx::load_workspace(root)?),
+            ResolverKind::Rush => Workspace::new(rush::load_workspace(root)?),
+            ResolverKind::Cargo => Workspace::new(cargo::load_workspace(root)?),
+            ResolverKind::Go => Workspace::new(go::load_workspace(root)?),
+            ResolverKind::External => Workspace::new(external::load_workspace(
+                root,
+                &resolver.command,
+                resolver
+                    .timeout
+                    .map(Duration::from_secs)
+                    .unwrap_or(external::DEFAULT_TIMEOUT),
+            )?),
+        })
+    }
+}
+
+impl fmt::Display for ResolverKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResolverKind::Pnpm => "pnpm",
+            ResolverKind::Nx => "nx",
+            ResolverKind::Rush => "rush",
+            ResolverKind::Cargo => "cargo",
+            ResolverKind::Go => "go",
+            ResolverKind::External => "external",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Find every ecosystem in the workspace root or one of its immediate subdirectories.
+/// A subdirectory is only considered for an ecosystem the root doesn't already belong to,
+/// so the members of a root Cargo workspace aren't picked up as workspaces of their own.
+pub fn detect(workspace_root: &Path) -> Result<Vec<ResolverConfig>> {
+    let mut subdirs = Vec::new();
+    for entry in std::fs::read_dir(workspace_root)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir()
+            && !name.starts_with('.')
+            && name != "node_modules"
+            && name != "target"
+        {
+            subdirs.push(name);
+        }
+    }
+    subdirs.sort();
+
+    let mut found = Vec::new();
+    for kind in ResolverKind::ALL {
+        if kind.detect(workspace_root) {
+            found.push(ResolverConfig {
+                kind,
+                path: None,
+                prefix: None,
+                command: vec![],
+                timeout: None,
+            });
+            continue;
+        }
+
+        let matches: Vec<_> = subdirs
+            .iter()
+            .filter(|dir| kind.detect(&workspace_root.join(dir)))
+            .collect();
+        for dir in &matches {
+            found.push(ResolverConfig {
+                kind,
+                path: Some(dir.into()),
+                // Several Go modules side by side need telling apart
+                prefix: (matches.len() > 1).then(|| format!("{}-{}", kind, dir)),
+                command: vec![],
+                timeout: None,
+            });
+        }
+    }
+
+    let superseded: Vec<_> = found
+        .iter()
+        .flat_map(|resolver| {
+            resolver
+                .kind
+                .supersedes()
+                .iter()
+                .map(|kind| (*kind, resolver.path.clone()))
+        })
+        .collect();
+    found.retain(|resolver| !superseded.contains(&(resolver.kind, resolver.path.clone())));
+
+    Ok(found)
+}
+
+/// Run the configured resolvers (or every detected one) and merge the results
+pub fn load_workspace(workspace_root: &Path, config: &BakehouseConfig) -> Result<Workspace> {
+    let resolvers = if config.resolvers.is_empty() {
+        detect(workspace_root)?
+    } else {
+        config.resolvers.clone()
+    };
+
+    if resolvers.is_empty() {
+        return Err(anyhow!(
+            "No supported workspace found in {}",
+            workspace_root.display()
+        ));
+    }
+
+    println!("Using resolvers:");
+    let mut parts = Vec::new();
+    for resolver in &resolvers {
+        let root = match &resolver.path {
+            Some(path) => workspace_root.join(path),
+            None => workspace_root.to_path_buf(),
+        };
+        println!("- {} at {}", resolver.kind, root.display());
+
+        let prefix = resolver
+            .prefix
+            .clone()
+            .unwrap_or_else(|| resolver.kind.to_string());
+        parts.push((prefix, resolver.kind.load(&root, resolver, config)?));
+    }
+
+    let mut workspace = Workspace::merge(parts)?;
+
+    // Templates in .bakehouse replace the resolvers' built-in ones, '.' for the root and
+    // globs for matching packages
+    if let Some(template) = config.templates.get(templates::ROOT_KEY) {
+        workspace
+            .dockerfile_template
+            .replace_template(template)
+            .with_context(|| format!("Failed to load template {} for the root", template.display()))?;
+    }
+    for (name, package) in workspace.packages.iter_mut() {
+        let path = package.path.strip_prefix(workspace_root).unwrap_or(&package.path);
+        if let Some(template) = config.find_template(path) {
+            package
+                .dockerfile_template
+                .replace_template(template)
+                .with_context(|| format!("Failed to load template {} for {}", template.display(), name))?;
+        }
+    }
+
+    let mut edges: Vec<_> = config.edges.iter().collect();
+    edges.sort();
+    for (from, deps) in edges {
+        for to in deps {
+            workspace.add_edge(from, to)?;
+        }
+    }
+
+    Ok(workspace)
+}
+
+/// Name for a resolver's root image: the directory name, unless a package already has it
+fn root_name(root: &Path, package_names: &[&str]) -> String {
+    let name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "workspace".to_string());
+
+    if package_names.contains(&name.as_str()) {
+        format!("{}-root", name)
+    } else {
+        name
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::write;
+    use tempfile::TempDir;
+
+    fn mixed_workspace() -> Result<TempDir> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+
+        write(root, "package.json", r#"{"name": "mono", "version": "1.0.0"}"#)?;
+        write(root, "pnpm-workspace.yaml", "packages:\n  - 'apps/*'\n")?;
+        write(root, "apps/web/package.json", r#"{"name": "@mono/web", "version": "1.0.0"}"#)?;
+
+        write(root, "rust/Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n")?;
+        write(
+            root,
+            "rust/crates/wasm-core/Cargo.toml",
+            "[package]\nname = \"wasm-core\"\nversion = \"0.2.0\"\n",
+        )?;
+
+        write(root, "billing/go.mod", "module github.com/mono/billing/v2\n\ngo 1.22\n")?;
+
+        Ok(temp_dir)
+    }
+
+    #[test]
+    fn test_detect() -> Result<()> {
+        let temp_dir = mixed_workspace()?;
+
+        let kinds: Vec<_> = detect(temp_dir.path())?
+            .into_iter()
+            .map(|resolver| (resolver.kind, resolver.path))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ResolverKind::Pnpm, None),
+                (ResolverKind::Cargo, Some("rust".into())),
+                (ResolverKind::Go, Some("billing".into())),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_nx_supersedes_pnpm() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write(temp_dir.path(), "pnpm-workspace.yaml", "packages: []\n")?;
+        write(temp_dir.path(), "nx.json", "{}")?;
+
+        let kinds: Vec<_> = detect(temp_dir.path())?
+            .into_iter()
+            .map(|resolver| resolver.kind)
+            .collect();
+        assert_eq!(kinds, vec![ResolverKind::Nx]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_mixed_workspace() -> Result<()> {
+        let temp_dir = mixed_workspace()?;
+        let mut config = BakehouseConfig::default();
+        config
+            .edges
+            .insert("pnpm-mono-web".to_string(), vec!["cargo-wasm-core".to_string()]);
+
+        let workspace = load_workspace(temp_dir.path(), &config)?;
+
+        assert_eq!(workspace.name, "pnpm-mono");
+        assert_eq!(workspace.dockerfile, "Dockerfile.pnpm.bake");
+
+        let mut names: Vec<_> = workspace.packages.keys().cloned().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "cargo-rust",
+                "cargo-wasm-core",
+                "go-billing",
+                "go-billing-root",
+                "pnpm-mono-web",
+            ]
+        );
+
+        let web = &workspace.packages["pnpm-mono-web"];
+        assert_eq!(web.root, "pnpm-mono");
+        assert!(web.dependencies.contains_key("cargo-wasm-core"));
+        let rendered = web.dockerfile_template.render()?;
+        assert!(rendered.contains("FROM pnpm-mono"));
+        assert!(rendered.contains("COPY --from=cargo-wasm-core /app/crates/wasm-core"));
+
+        // The Go module shares its directory with the Go root
+        assert_eq!(
+            workspace.packages["go-billing"].dockerfile,
+            "Dockerfile.billing.bake"
+        );
+        assert_eq!(
+            workspace.packages["go-billing-root"].dockerfile,
+            "Dockerfile.go.bake"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mixed_workspace_references() -> Result<()> {
+        let temp_dir = mixed_workspace()?;
+        let root = temp_dir.path();
+        write(
+            root,
+            "apps/web/package.json",
+            r#"{"name": "@mono/web", "version": "1.0.0", "dependencies": {"@mono/core": "workspace:*"}}"#,
+        )?;
+        write(root, "apps/web/tsconfig.json", r#"{"references": [{"path": "../core"}]}"#)?;
+        write(root, "apps/core/package.json", r#"{"name": "@mono/core", "version": "1.0.0"}"#)?;
+        write(root, "apps/core/tsconfig.json", r#"{"compilerOptions": {"outDir": "./dist"}}"#)?;
+        let config = BakehouseConfig {
+            typescript_references: pnpm::tsconfig::ReferenceMode::Report,
+            ..Default::default()
+        };
+
+        // References are named after the prefixed targets, like dependencies
+        let workspace = load_workspace(root, &config)?;
+        let rendered = workspace.packages["pnpm-mono-web"].dockerfile_template.render()?;
+        assert!(rendered.contains(
+            "COPY --from=pnpm-mono-core /app/apps/core/tsconfig.json /app/apps/core/tsconfig.json"
+        ));
+        assert!(rendered.contains("COPY --from=pnpm-mono-core /app/apps/core/dist /app/apps/core/dist"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_templates() -> Result<()> {
+        let temp_dir = mixed_workspace()?;
+        std::fs::write(
+            temp_dir.path().join("web.tera"),
+            "---\nstages: [build]\n---\nFROM {{ root }} AS build\nCOPY . /app/{{ path }}\n",
+        )?;
+        let mut config = BakehouseConfig::default();
+        config.templates.insert("apps/*".to_string(), temp_dir.path().join("web.tera"));
+
+        let workspace = load_workspace(temp_dir.path(), &config)?;
+        let web = &workspace.packages["pnpm-mono-web"].dockerfile_template;
+        assert_eq!(web.render()?, "FROM pnpm-mono AS build\nCOPY . /app/apps/web\n");
+        assert_eq!(web.front_matter.stages.as_deref(), Some(&["build".to_string()][..]));
+        assert!(workspace.packages["cargo-rust"].dockerfile_template.front_matter.stages.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    dockerfile::DockerfileTemplate,
+    workspace::{PackageInfo, PackageMetadata, WorkspaceInfo},
+};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+pub mod model;
+use model::*;
+
+use super::root_name;
+
+#[derive(Debug, Clone)]
+pub struct CargoPackageInfo {
+    name: String,
+    version: String,
+    path: PathBuf,
+    dependencies: HashSet<String>,
+    metadata: Option<PackageMetadata>,
+    dockerfile_template: DockerfileTemplate,
+}
+
+impl PackageInfo for CargoPackageInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn dependencies(&self) -> &HashSet<String> {
+        &self.dependencies
+    }
+
+    fn dockerfile_template(&self) -> &DockerfileTemplate {
+        &self.dockerfile_template
+    }
+
+    fn metadata(&self) -> Option<&PackageMetadata> {
+        self.metadata.as_ref()
+    }
+}
+
+#[derive(Debug)]
+pub struct CargoWorkspaceInfo {
+    root_package: CargoPackageInfo,
+    packages: Vec<CargoPackageInfo>,
+}
+
+impl WorkspaceInfo for CargoWorkspaceInfo {
+    fn root_package(&self) -> &dyn PackageInfo {
+        &self.root_package
+    }
+
+    fn packages(&self) -> Vec<&dyn PackageInfo> {
+        self.packages
+            .iter()
+            .map(|p| p as &dyn PackageInfo)
+            .collect()
+    }
+}
+
+pub fn load_workspace(workspace_root: &Path) -> Result<CargoWorkspaceInfo> {
+    let root_toml = load_cargo_toml(&workspace_root.join("Cargo.toml"))?;
+    let workspace_package = root_toml
+        .workspace
+        .as_ref()
+        .and_then(|workspace| workspace.package.clone());
+
+    let mut packages = Vec::new();
+
+    // A root manifest with a [package] section is a crate in its own right
+    if root_toml.package.is_some() {
+        packages.push(load_crate(workspace_root, &root_toml, workspace_package.as_ref())?);
+    }
+
+    if let Some(workspace) = &root_toml.workspace {
+        for member_dir in expand_members(workspace_root, &workspace.members, &workspace.exclude)? {
+            if member_dir == workspace_root {
+                continue;
+            }
+            let member_toml = load_cargo_toml(&member_dir.join("Cargo.toml"))?;
+            packages.push(load_crate(&member_dir, &member_toml, workspace_package.as_ref())?);
+        }
+    }
+
+    let rust_version = workspace_package
+        .as_ref()
+        .and_then(|package| package.rust_version.clone())
+        .or_else(|| {
+            root_toml
+                .package
+                .as_ref()
+                .and_then(|package| package.rust_version.as_ref())
+                .and_then(|version| version.as_str().map(str::to_string))
+        })
+        .unwrap_or_default();
+
+    let package_names: Vec<_> = packages.iter().map(|p| p.name.as_str()).collect();
+    let root_package = CargoPackageInfo {
+        name: root_name(workspace_root, &package_names),
+        version: workspace_package
+            .and_then(|package| package.version)
+            .unwrap_or_else(|| "0.0.0".to_string()),
+        path: workspace_root.to_path_buf(),
+        dependencies: HashSet::new(),
+        metadata: None,
+        dockerfile_template: {
+            let mut template = DockerfileTemplate::builtin("Dockerfile.cargo.root.tera")?;
+            template.context.insert("rust_version", &rust_version);
+            template
+        },
+    };
+
+    Ok(CargoWorkspaceInfo {
+        root_package,
+        packages,
+    })
+}
+
+fn load_crate(
+    crate_dir: &Path,
+    cargo_toml: &CargoToml,
+    workspace_package: Option<&WorkspacePackage>,
+) -> Result<CargoPackageInfo> {
+    let package = cargo_toml
+        .package
+        .as_ref()
+        .with_context(|| format!("No [package] in {}", crate_dir.join("Cargo.toml").display()))?;
+
+    // `version.workspace = true` inherits from [workspace.package]
+    let version = match &package.version {
+        Some(toml::Value::String(version)) => version.clone(),
+        _ => workspace_package
+            .and_then(|package| package.version.clone())
+            .unwrap_or_else(|| "0.0.0".to_string()),
+    };
+
+    // Renamed dependencies (`foo = { package = "bar" }`) are known to the workspace by their real name
+    let dependencies = cargo_toml
+        .dependencies
+        .iter()
+        .chain(&cargo_toml.dev_dependencies)
+        .chain(&cargo_toml.build_dependencies)
+        .map(|(name, spec)| {
+            spec.get("package")
+                .and_then(|package| package.as_str())
+                .unwrap_or(name)
+                .to_string()
+        })
+        .collect();
+
+    let mut dockerfile_template = DockerfileTemplate::builtin("Dockerfile.cargo.bake.tera")?;
+    dockerfile_template.context.insert("crate_name", &package.name);
+
+    Ok(CargoPackageInfo {
+        name: package.name.clone(),
+        version,
+        path: crate_dir.to_path_buf(),
+        dependencies,
+        metadata: Some(metadata(package, workspace_package)),
+        dockerfile_template,
+    })
+}
+
+/// What image labels are made from, with `{ workspace = true }` fields taken from
+/// [workspace.package]
+fn metadata(package: &CargoPackage, workspace_package: Option<&WorkspacePackage>) -> PackageMetadata {
+    let field = |value: &Option<toml::Value>, inherited: fn(&WorkspacePackage) -> Option<String>| match value {
+        Some(toml::Value::String(value)) => Some(value.clone()),
+        Some(toml::Value::Table(_)) => workspace_package.and_then(inherited),
+        _ => None,
+    };
+    let authors = match &package.authors {
+        Some(toml::Value::Array(authors)) => authors
+            .iter()
+            .filter_map(|author| author.as_str())
+            .map(str::to_string)
+            .collect(),
+        Some(toml::Value::Table(_)) => workspace_package.map(|package| package.authors.clone()).unwrap_or_default(),
+        _ => vec![],
+    };
+
+    PackageMetadata {
+        description: field(&package.description, |package| package.description.clone()),
+        license: field(&package.license, |package| package.license.clone()),
+        repository: field(&package.repository, |package| package.repository.clone()),
+        homepage: field(&package.homepage, |package| package.homepage.clone()),
+        author: (!authors.is_empty()).then(|| authors.join(", ")),
+    }
+}
+
+/// Expand `[workspace] members` globs into crate directories, honouring `exclude`
+fn expand_members(workspace_root: &Path, members: &[String], exclude: &[String]) -> Result<Vec<PathBuf>> {
+    let excluded: HashSet<_> = exclude.iter().map(|dir| workspace_root.join(dir)).collect();
+
+    let mut dirs = Vec::new();
+    for member in members {
+        let pattern = workspace_root.join(member);
+        for entry in glob::glob(&pattern.to_string_lossy())? {
+            let dir = entry?;
+            if dir.join("Cargo.toml").is_file() && !excluded.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs.sort();
+    dirs.dedup();
+
+    Ok(dirs)
+}
+
+fn load_cargo_toml(path: &Path) -> Result<CargoToml> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolvers::test_support::write;
+    use crate::workspace::Workspace;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_workspace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path().join("engine");
+        write(
+            &root,
+            "Cargo.toml",
+            "[package]\nname = \"engine\"\nversion = \"2.0.0\"\n\n\
+             [dependencies]\nparser = { path = \"crates/parser\" }\n\n\
+             [workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/scratch\"]\n\n\
+             [workspace.package]\nversion = \"1.4.0\"\nrust-version = \"1.79\"\nlicense = \"MIT\"\n\
+             authors = [\"Jane Doe <jane@example.com>\"]\n",
+        )?;
+        write(
+            &root,
+            "crates/parser/Cargo.toml",
+            "[package]\nname = \"parser\"\nversion.workspace = true\nlicense.workspace = true\n\
+             authors.workspace = true\ndescription = \"Parses things\"\n\
+             repository = \"https://github.com/acme/engine\"\n",
+        )?;
+        write(
+            &root,
+            "crates/cli/Cargo.toml",
+            "[package]\nname = \"cli\"\nversion = \"0.3.0\"\n\n\
+             [dependencies]\nsyntax = { package = \"parser\", path = \"../parser\" }\nserde = \"1\"\n\n\
+             [dev-dependencies]\nengine = { path = \"../..\" }\n",
+        )?;
+        write(&root, "crates/scratch/Cargo.toml", "[package]\nname = \"scratch\"\n")?;
+
+        let workspace = Workspace::new(load_workspace(&root)?);
+        assert_eq!(workspace.name, "engine-root");
+        assert_eq!(workspace.version, "1.4.0");
+
+        let mut names: Vec<_> = workspace.packages.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, vec!["cli", "engine", "parser"]);
+
+        // The crate in the root directory gets a Dockerfile name of its own
+        let engine = &workspace.packages["engine"];
+        assert_eq!(engine.version, "2.0.0");
+        assert_eq!(engine.dockerfile, "Dockerfile.engine.bake");
+        assert!(engine.dependencies.contains_key("parser"));
+
+        // Renamed and dev dependencies are workspace dependencies too, crates.io ones aren't
+        let cli = &workspace.packages["cli"];
+        let mut deps: Vec<_> = cli.dependencies.keys().cloned().collect();
+        deps.sort();
+        assert_eq!(deps, vec!["engine", "engine-root", "parser"]);
+        assert!(cli.dockerfile_template.render()?.contains("RUN cargo build --release -p cli"));
+
+        let parser = &workspace.packages["parser"];
+        assert_eq!(parser.version, "1.4.0");
+        assert_eq!(
+            parser.metadata,
+            PackageMetadata {
+                description: Some("Parses things".to_string()),
+                license: Some("MIT".to_string()),
+                repository: Some("https://github.com/acme/engine".to_string()),
+                homepage: None,
+                author: Some("Jane Doe <jane@example.com>".to_string()),
+            }
+        );
+        assert_eq!(cli.metadata, PackageMetadata::default());
+        assert!(workspace.dockerfile_template.render()?.starts_with("FROM rust:1.79-slim"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crate_without_package() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        write(temp_dir.path(), "Cargo.toml", "[workspace]\nmembers = [\"tools\"]\n")?;
+        write(temp_dir.path(), "tools/Cargo.toml", "[dependencies]\n")?;
+
+        assert!(load_workspace(temp_dir.path()).is_err());
+
+        Ok(())
+    }
+}
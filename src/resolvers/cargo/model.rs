@@ -0,0 +1,51 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CargoToml {
+    pub package: Option<CargoPackage>,
+    pub workspace: Option<CargoWorkspace>,
+    #[serde(default)]
+    pub dependencies: HashMap<String, toml::Value>,
+    #[serde(default, rename = "dev-dependencies")]
+    pub dev_dependencies: HashMap<String, toml::Value>,
+    #[serde(default, rename = "build-dependencies")]
+    pub build_dependencies: HashMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CargoPackage {
+    pub name: String,
+    /// Either a version string or `{ workspace = true }`
+    pub version: Option<toml::Value>,
+    #[serde(rename = "rust-version")]
+    pub rust_version: Option<toml::Value>,
+    /// Metadata for image labels, each a value or `{ workspace = true }`
+    pub description: Option<toml::Value>,
+    pub license: Option<toml::Value>,
+    pub repository: Option<toml::Value>,
+    pub homepage: Option<toml::Value>,
+    pub authors: Option<toml::Value>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CargoWorkspace {
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    pub package: Option<WorkspacePackage>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WorkspacePackage {
+    pub version: Option<String>,
+    #[serde(rename = "rust-version")]
+    pub rust_version: Option<String>,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub repository: Option<String>,
+    pub homepage: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+}
@@ -0,0 +1,344 @@
+use crate::{
+    dockerfile::DockerfileTemplate,
+    workspace::{DependencyKind, PackageInfo, WorkspaceInfo},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+pub mod model;
+use model::*;
+
+/// How long an external resolver may run when `.bakehouse` doesn't say
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct ExternalPackageInfo {
+    name: String,
+    version: String,
+    path: PathBuf,
+    dependencies: HashSet<String>,
+    dependency_kinds: HashMap<String, DependencyKind>,
+    dockerfile_template: DockerfileTemplate,
+}
+
+impl PackageInfo for ExternalPackageInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn dependencies(&self) -> &HashSet<String> {
+        &self.dependencies
+    }
+
+    fn dockerfile_template(&self) -> &DockerfileTemplate {
+        &self.dockerfile_template
+    }
+
+    fn dependency_kinds(&self) -> Option<&HashMap<String, DependencyKind>> {
+        Some(&self.dependency_kinds)
+    }
+}
+
+#[derive(Debug)]
+pub struct ExternalWorkspaceInfo {
+    root_package: ExternalPackageInfo,
+    packages: Vec<ExternalPackageInfo>,
+}
+
+impl WorkspaceInfo for ExternalWorkspaceInfo {
+    fn root_package(&self) -> &dyn PackageInfo {
+        &self.root_package
+    }
+
+    fn packages(&self) -> Vec<&dyn PackageInfo> {
+        self.packages
+            .iter()
+            .map(|p| p as &dyn PackageInfo)
+            .collect()
+    }
+}
+
+/// Run an external resolver and turn its answer into a workspace.
+///
+/// The executable receives a [`ResolveRequest`] as JSON on stdin and must print a
+/// [`ResolveResponse`] as JSON on stdout before `timeout` elapses.
+pub fn load_workspace(
+    workspace_root: &Path,
+    command: &[String],
+    timeout: Duration,
+) -> Result<ExternalWorkspaceInfo> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("External resolver has no `command`"))?;
+    let display = command.join(" ");
+
+    let request = serde_json::to_string(&ResolveRequest {
+        version: PROTOCOL_VERSION,
+        workspace_root: workspace_root.to_path_buf(),
+    })?;
+
+    let output = run(workspace_root, program, args, &request, timeout)
+        .with_context(|| format!("External resolver `{}` failed", display))?;
+
+    let response = parse_response(&output)
+        .with_context(|| format!("External resolver `{}` returned an invalid document", display))?;
+
+    let mut names = HashSet::new();
+    for package in std::iter::once(&response.root).chain(&response.packages) {
+        if !names.insert(package.name.as_str()) {
+            bail!(
+                "External resolver `{}` returned package '{}' more than once",
+                display,
+                package.name
+            );
+        }
+    }
+
+    Ok(ExternalWorkspaceInfo {
+        root_package: to_package_info(workspace_root, response.root)?,
+        packages: response
+            .packages
+            .into_iter()
+            .map(|package| to_package_info(workspace_root, package))
+            .collect::<Result<_>>()?,
+    })
+}
+
+fn run(
+    workspace_root: &Path,
+    program: &str,
+    args: &[String],
+    input: &str,
+    timeout: Duration,
+) -> Result<String> {
+    // Relative executables such as `./tools/resolve` live in the workspace
+    let program = if program.contains('/') {
+        workspace_root.join(program)
+    } else {
+        PathBuf::from(program)
+    };
+
+    let mut child = Command::new(&program)
+        .args(args)
+        .current_dir(workspace_root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start {}", program.display()))?;
+
+    // A resolver that exits without reading stdin is fine, so a broken pipe isn't an error
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input.as_bytes());
+    }
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stdout_reader = thread::spawn(move || {
+        let mut buffer = String::new();
+        stdout.read_to_string(&mut buffer).map(|_| buffer)
+    });
+    let stderr_reader = thread::spawn(move || {
+        let mut buffer = String::new();
+        let _ = stderr.read_to_string(&mut buffer);
+        buffer
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("Timed out after {}s", timeout.as_secs_f32());
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let stdout = stdout_reader
+        .join()
+        .map_err(|_| anyhow!("Failed to read stdout"))??;
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if !status.success() {
+        bail!("Exited with {}: {}", status, stderr.trim());
+    }
+
+    Ok(stdout)
+}
+
+fn parse_response(output: &str) -> Result<ResolveResponse> {
+    // Check the version on its own first, so a resolver written for another protocol
+    // version gets told that rather than about whichever field changed
+    #[derive(Deserialize)]
+    struct Versioned {
+        version: Option<u32>,
+    }
+
+    let versioned: Versioned = serde_json::from_str(output).context("Output is not valid JSON")?;
+    match versioned.version {
+        Some(PROTOCOL_VERSION) => {}
+        Some(version) => bail!(
+            "Unsupported protocol version {} (expected {})",
+            version,
+            PROTOCOL_VERSION
+        ),
+        None => bail!("Missing protocol `version` (expected {})", PROTOCOL_VERSION),
+    }
+
+    serde_json::from_str(output).map_err(|e| anyhow!("Schema error: {}", e))
+}
+
+fn to_package_info(workspace_root: &Path, package: ExternalPackage) -> Result<ExternalPackageInfo> {
+    let template_path = workspace_root.join(&package.template);
+    let mut dockerfile_template = DockerfileTemplate::new(&template_path).with_context(|| {
+        format!(
+            "Failed to load template {} for package '{}'",
+            template_path.display(),
+            package.name
+        )
+    })?;
+
+    for (key, value) in &package.variables {
+        dockerfile_template.context.insert(key, value);
+    }
+
+    let dependency_kinds: HashMap<_, _> = package
+        .dependencies
+        .iter()
+        .map(|dep| (dep.name.clone(), dep.kind))
+        .collect();
+
+    Ok(ExternalPackageInfo {
+        name: package.name,
+        version: package.version,
+        path: workspace_root.join(&package.path),
+        dependencies: dependency_kinds.keys().cloned().collect(),
+        dependency_kinds,
+        dockerfile_template,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() -> Result<()> {
+        let response = parse_response(
+            r#"{
+                "version": 1,
+                "root": {"name": "base", "path": ".", "version": "1.0.0", "template": "root.tera"},
+                "packages": [{
+                    "name": "svc",
+                    "path": "services/svc",
+                    "version": "2.0.0",
+                    "dependencies": [{"name": "lib", "kind": "build"}, {"name": "base"}],
+                    "template": "svc.tera",
+                    "variables": {"jdk": 21}
+                }]
+            }"#,
+        )?;
+
+        assert_eq!(response.root.name, "base");
+        assert_eq!(response.packages[0].dependencies.len(), 2);
+        assert_eq!(response.packages[0].variables["jdk"], 21);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_response_errors() {
+        let version = parse_response(r#"{"version": 2}"#).unwrap_err();
+        assert_eq!(version.to_string(), "Unsupported protocol version 2 (expected 1)");
+
+        let missing = parse_response(r#"{"root": {}}"#).unwrap_err();
+        assert_eq!(missing.to_string(), "Missing protocol `version` (expected 1)");
+
+        let schema = parse_response(
+            r#"{"version": 1, "root": {"name": "base", "path": ".", "template": "root.tera"}}"#,
+        )
+        .unwrap_err();
+        assert!(schema.to_string().starts_with("Schema error: missing field `version`"));
+    }
+
+    // The resolvers below are shell scripts and `sleep`
+    #[cfg(unix)]
+    #[test]
+    fn test_load_workspace() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        std::fs::write(root.join("root.tera"), "FROM debian")?;
+        std::fs::write(
+            root.join("svc.tera"),
+            "FROM {{ root }}\nENV JDK={{ jdk }}\n{% for dep in dependencies %}# {{ dep.name }}: {{ dep.kind }}\n{% endfor %}",
+        )?;
+
+        let script = root.join("resolve.sh");
+        std::fs::write(
+            &script,
+            r#"#!/bin/sh
+grep -q '"version":1' || exit 1
+cat <<JSON
+{
+  "version": 1,
+  "root": {"name": "base", "path": ".", "version": "1.0.0", "template": "root.tera"},
+  "packages": [
+    {"name": "svc", "path": "svc", "version": "2.0.0", "template": "svc.tera",
+     "dependencies": [{"name": "base"}, {"name": "@acme/Proto", "kind": "build"}, {"name": "util"}],
+     "variables": {"jdk": 21}},
+    {"name": "@acme/Proto", "path": "proto", "version": "1.0.0", "template": "root.tera"},
+    {"name": "util", "path": "util", "version": "1.0.0", "template": "root.tera"}
+  ]
+}
+JSON
+"#,
+        )?;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+
+        let workspace = crate::workspace::Workspace::new(load_workspace(
+            root,
+            &["./resolve.sh".to_string()],
+            DEFAULT_TIMEOUT,
+        )?);
+
+        assert_eq!(workspace.name, "base");
+        let svc = &workspace.packages["svc"];
+        assert_eq!(svc.path, root.join("svc"));
+        assert_eq!(
+            svc.dockerfile_template.render()?,
+            "FROM base\nENV JDK=21\n# acme-proto: build\n# util: runtime\n"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let command = vec!["sleep".to_string(), "5".to_string()];
+
+        let error = load_workspace(temp_dir.path(), &command, Duration::from_millis(100)).unwrap_err();
+        assert!(format!("{:#}", error).contains("Timed out after 0.1s"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::workspace::DependencyKind;
+
+/// Version of the JSON protocol spoken with external resolvers
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Sent to the resolver on stdin
+#[derive(Debug, Serialize)]
+pub struct ResolveRequest {
+    pub version: u32,
+    pub workspace_root: PathBuf,
+}
+
+/// Expected back from the resolver on stdout
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResolveResponse {
+    /// Checked on its own before the rest of the document is parsed
+    #[allow(dead_code)]
+    pub version: u32,
+    pub root: ExternalPackage,
+    #[serde(default)]
+    pub packages: Vec<ExternalPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalPackage {
+    pub name: String,
+    /// Relative to the workspace root
+    pub path: PathBuf,
+    pub version: String,
+    #[serde(default)]
+    pub dependencies: Vec<ExternalDependency>,
+    /// Tera template for the package's Dockerfile, relative to the workspace root
+    pub template: PathBuf,
+    /// Extra values made available to the template
+    #[serde(default)]
+    pub variables: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalDependency {
+    pub name: String,
+    #[serde(default)]
+    pub kind: DependencyKind,
+}
@@ -0,0 +1,233 @@
+use crate::{
+    dockerfile::DockerfileTemplate,
+    workspace::{PackageInfo, WorkspaceInfo},
+};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+pub mod model;
+use model::*;
+
+use super::root_name;
+
+#[derive(Debug, Clone)]
+pub struct GoPackageInfo {
+    name: String,
+    version: String,
+    path: PathBuf,
+    dependencies: HashSet<String>,
+    dockerfile_template: DockerfileTemplate,
+}
+
+impl PackageInfo for GoPackageInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn dependencies(&self) -> &HashSet<String> {
+        &self.dependencies
+    }
+
+    fn dockerfile_template(&self) -> &DockerfileTemplate {
+        &self.dockerfile_template
+    }
+}
+
+#[derive(Debug)]
+pub struct GoWorkspaceInfo {
+    root_package: GoPackageInfo,
+    packages: Vec<GoPackageInfo>,
+}
+
+impl WorkspaceInfo for GoWorkspaceInfo {
+    fn root_package(&self) -> &dyn PackageInfo {
+        &self.root_package
+    }
+
+    fn packages(&self) -> Vec<&dyn PackageInfo> {
+        self.packages
+            .iter()
+            .map(|p| p as &dyn PackageInfo)
+            .collect()
+    }
+}
+
+/// Go modules don't carry a version of their own
+const MODULE_VERSION: &str = "0.0.0";
+
+pub fn load_workspace(workspace_root: &Path) -> Result<GoWorkspaceInfo> {
+    // A go.work lists the modules explicitly, otherwise the root is a single module
+    let go_work_path = workspace_root.join("go.work");
+    let (go_version, module_dirs) = if go_work_path.is_file() {
+        let go_work = GoWork::parse(&read(&go_work_path)?);
+        let dirs = go_work
+            .uses
+            .iter()
+            .map(|dir| workspace_root.join(dir.trim_start_matches("./")))
+            .collect::<Vec<_>>();
+        (go_work.go_version, dirs)
+    } else {
+        (None, vec![workspace_root.to_path_buf()])
+    };
+
+    let mut modules = Vec::new();
+    for dir in module_dirs {
+        let go_mod = GoMod::parse(&read(&dir.join("go.mod"))?);
+        modules.push((dir, go_mod));
+    }
+
+    // Packages are named after the last element of their module path, skipping major
+    // version suffixes, so `github.com/acme/billing/v2` becomes `billing`
+    let names: HashMap<String, String> = modules
+        .iter()
+        .map(|(_, go_mod)| (go_mod.module.clone(), module_name(&go_mod.module)))
+        .collect();
+
+    let mut packages = Vec::new();
+    for (dir, go_mod) in &modules {
+        let dependencies = go_mod
+            .requires
+            .iter()
+            .filter_map(|module| names.get(module).cloned())
+            .collect();
+
+        let mut dockerfile_template = DockerfileTemplate::builtin("Dockerfile.go.bake.tera")?;
+        dockerfile_template.context.insert("module", &go_mod.module);
+
+        packages.push(GoPackageInfo {
+            name: names[&go_mod.module].clone(),
+            version: MODULE_VERSION.to_string(),
+            path: dir.clone(),
+            dependencies,
+            dockerfile_template,
+        });
+    }
+
+    let go_version = go_version
+        .or_else(|| modules.iter().find_map(|(_, go_mod)| go_mod.go_version.clone()))
+        .unwrap_or_default();
+
+    let package_names: Vec<_> = packages.iter().map(|p| p.name.as_str()).collect();
+    let root_package = GoPackageInfo {
+        name: root_name(workspace_root, &package_names),
+        version: MODULE_VERSION.to_string(),
+        path: workspace_root.to_path_buf(),
+        dependencies: HashSet::new(),
+        dockerfile_template: {
+            let mut template = DockerfileTemplate::builtin("Dockerfile.go.root.tera")?;
+            template.context.insert("go_version", &go_version);
+            template
+        },
+    };
+
+    Ok(GoWorkspaceInfo {
+        root_package,
+        packages,
+    })
+}
+
+fn module_name(module: &str) -> String {
+    let mut segments = module.rsplit('/');
+    let last = segments.next().unwrap_or(module);
+    let is_major_version = last.len() > 1
+        && last.starts_with('v')
+        && last[1..].chars().all(|c| c.is_ascii_digit());
+
+    if is_major_version {
+        segments.next().unwrap_or(last).to_string()
+    } else {
+        last.to_string()
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolvers::test_support::write;
+    use crate::workspace::Workspace;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        let go_mod = GoMod::parse(
+            "// The billing service\n\
+             module \"github.com/acme/billing/v2\"\n\n\
+             go 1.22\n\n\
+             require (\n\
+             \tgithub.com/acme/ledger v0.0.0 // indirect\n\
+             \tgopkg.in/yaml.v3 v3.0.1\n\
+             )\n\
+             require golang.org/x/sync v0.7.0\n",
+        );
+        assert_eq!(go_mod.module, "github.com/acme/billing/v2");
+        assert_eq!(go_mod.go_version.as_deref(), Some("1.22"));
+        assert_eq!(go_mod.requires, ["github.com/acme/ledger", "gopkg.in/yaml.v3", "golang.org/x/sync"]);
+
+        let go_work = GoWork::parse("go 1.23\n\nuse (\n\t./billing\n\t\"./ledger\"\n)\nuse ./tools\n");
+        assert_eq!(go_work.go_version.as_deref(), Some("1.23"));
+        assert_eq!(go_work.uses, ["./billing", "./ledger", "./tools"]);
+
+        assert_eq!(module_name("github.com/acme/billing/v2"), "billing");
+        assert_eq!(module_name("gopkg.in/yaml.v3"), "yaml.v3");
+        assert_eq!(module_name("v2"), "v2");
+    }
+
+    #[test]
+    fn test_load_workspace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path().join("acme");
+        write(&root, "go.work", "go 1.23\n\nuse (\n\t./billing\n\t./yaml\n)\n")?;
+        write(
+            &root,
+            "billing/go.mod",
+            "module github.com/acme/billing/v2\n\ngo 1.22\n\nrequire (\n\tgopkg.in/yaml.v3 v3.0.1\n\tgolang.org/x/sync v0.7.0\n)\n",
+        )?;
+        write(&root, "yaml/go.mod", "module gopkg.in/yaml.v3\n")?;
+
+        let workspace = Workspace::new(load_workspace(&root)?);
+        assert_eq!(workspace.name, "acme");
+        assert!(workspace.dockerfile_template.render()?.starts_with("FROM golang:1.23-alpine"));
+
+        // Dots aren't allowed in bake target names
+        let mut names: Vec<_> = workspace.packages.keys().cloned().collect();
+        names.sort();
+        assert_eq!(names, vec!["billing", "yaml-v3"]);
+
+        let billing = &workspace.packages["billing"];
+        assert_eq!(billing.version, MODULE_VERSION);
+        let mut deps: Vec<_> = billing.dependencies.keys().cloned().collect();
+        deps.sort();
+        assert_eq!(deps, vec!["acme", "yaml-v3"]);
+        let dockerfile = billing.dockerfile_template.render()?;
+        assert!(dockerfile.contains("COPY --from=yaml-v3 /app/yaml /app/yaml"));
+        assert!(dockerfile.contains("# Download modules and build github.com/acme/billing/v2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_module() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path().join("tool");
+        write(&root, "go.mod", "module example.com/tool\n\ngo 1.21\n")?;
+
+        let workspace = Workspace::new(load_workspace(&root)?);
+        assert_eq!(workspace.name, "tool-root");
+        assert_eq!(workspace.packages["tool"].dockerfile, "Dockerfile.tool.bake");
+        assert!(workspace.dockerfile_template.render()?.starts_with("FROM golang:1.21-alpine"));
+
+        Ok(())
+    }
+}
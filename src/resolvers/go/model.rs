@@ -0,0 +1,84 @@
+/// The parts of a `go.mod` file bakehouse cares about
+#[derive(Debug, Clone, Default)]
+pub struct GoMod {
+    pub module: String,
+    pub go_version: Option<String>,
+    pub requires: Vec<String>,
+}
+
+/// The parts of a `go.work` file bakehouse cares about
+#[derive(Debug, Clone, Default)]
+pub struct GoWork {
+    pub go_version: Option<String>,
+    pub uses: Vec<String>,
+}
+
+impl GoMod {
+    pub fn parse(content: &str) -> Self {
+        let mut go_mod = GoMod::default();
+
+        for (directive, args) in directives(content) {
+            match directive.as_str() {
+                "module" => go_mod.module = args.trim_matches('"').to_string(),
+                "go" => go_mod.go_version = Some(args),
+                "require" => {
+                    if let Some(module) = args.split_whitespace().next() {
+                        go_mod.requires.push(module.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        go_mod
+    }
+}
+
+impl GoWork {
+    pub fn parse(content: &str) -> Self {
+        let mut go_work = GoWork::default();
+
+        for (directive, args) in directives(content) {
+            match directive.as_str() {
+                "go" => go_work.go_version = Some(args),
+                "use" => go_work.uses.push(args.trim_matches('"').to_string()),
+                _ => {}
+            }
+        }
+
+        go_work
+    }
+}
+
+/// Flatten `go.mod`/`go.work` syntax into `(directive, arguments)` pairs, expanding
+/// `require ( ... )` style blocks into one pair per line
+fn directives(content: &str) -> Vec<(String, String)> {
+    let mut result = Vec::new();
+    let mut block: Option<String> = None;
+
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(directive) = &block {
+            if line == ")" {
+                block = None;
+            } else {
+                result.push((directive.clone(), line.to_string()));
+            }
+            continue;
+        }
+
+        let (directive, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+        if args == "(" {
+            block = Some(directive.to_string());
+        } else {
+            result.push((directive.to_string(), args.to_string()));
+        }
+    }
+
+    result
+}
@@ -0,0 +1,345 @@
+use crate::{
+    dockerfile::DockerfileTemplate,
+    workspace::{PackageInfo, PackageMetadata, WorkspaceInfo},
+};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+pub mod model;
+use model::*;
+
+use super::pnpm::{is_hidden, load_package_json};
+use super::root_name;
+
+#[derive(Debug, Clone)]
+pub struct NxPackageInfo {
+    name: String,
+    version: String,
+    path: PathBuf,
+    dependencies: HashSet<String>,
+    tags: Vec<String>,
+    deployable: bool,
+    metadata: Option<PackageMetadata>,
+    dockerfile_template: DockerfileTemplate,
+}
+
+impl PackageInfo for NxPackageInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn dependencies(&self) -> &HashSet<String> {
+        &self.dependencies
+    }
+
+    fn dockerfile_template(&self) -> &DockerfileTemplate {
+        &self.dockerfile_template
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn deployable(&self) -> bool {
+        self.deployable
+    }
+
+    fn metadata(&self) -> Option<&PackageMetadata> {
+        self.metadata.as_ref()
+    }
+}
+
+#[derive(Debug)]
+pub struct NxWorkspaceInfo {
+    root_package: NxPackageInfo,
+    packages: Vec<NxPackageInfo>,
+}
+
+impl WorkspaceInfo for NxWorkspaceInfo {
+    fn root_package(&self) -> &dyn PackageInfo {
+        &self.root_package
+    }
+
+    fn packages(&self) -> Vec<&dyn PackageInfo> {
+        self.packages
+            .iter()
+            .map(|p| p as &dyn PackageInfo)
+            .collect()
+    }
+}
+
+/// A project as found on disk, before dependencies are resolved against the other projects
+struct NxProject {
+    name: String,
+    path: PathBuf,
+    project_json: ProjectJson,
+    package_json_name: Option<String>,
+    version: Option<String>,
+    /// From the project's package.json, when it has one
+    metadata: Option<PackageMetadata>,
+    npm_dependencies: HashSet<String>,
+}
+
+pub fn load_workspace(workspace_root: &Path) -> Result<NxWorkspaceInfo> {
+    let nx_json = load_nx_json(&workspace_root.join("nx.json"))?;
+    let root_json = load_package_json(&workspace_root.join("package.json")).ok();
+
+    let projects = discover_projects(workspace_root)?;
+
+    // Projects can reference each other by Nx name or, through package.json, by npm name
+    let by_npm_name: HashMap<&str, &str> = projects
+        .iter()
+        .filter_map(|p| Some((p.package_json_name.as_deref()?, p.name.as_str())))
+        .collect();
+    let project_names: HashSet<&str> = projects.iter().map(|p| p.name.as_str()).collect();
+
+    let mut packages = Vec::new();
+    for project in &projects {
+        let mut dependencies: HashSet<String> = project
+            .npm_dependencies
+            .iter()
+            .filter_map(|dep| by_npm_name.get(dep.as_str()))
+            .map(|name| name.to_string())
+            .collect();
+
+        for implicit in &project.project_json.implicit_dependencies {
+            if implicit == "*" {
+                dependencies.extend(
+                    project_names
+                        .iter()
+                        .filter(|name| **name != project.name)
+                        .map(|name| name.to_string()),
+                );
+            } else if let Some(excluded) = implicit.strip_prefix('!') {
+                dependencies.remove(excluded);
+            } else {
+                dependencies.insert(implicit.clone());
+            }
+        }
+
+        // Project targets fall back to nx.json `targetDefaults` for anything they leave out
+        let mut target_names: Vec<_> = project.project_json.targets.keys().collect();
+        target_names.sort();
+        let targets: Vec<_> = target_names
+            .into_iter()
+            .map(|name| {
+                let target = &project.project_json.targets[name];
+                let defaults = nx_json.target_defaults.get(name).cloned().unwrap_or_default();
+                serde_json::json!({
+                    "name": name,
+                    "executor": target.executor.clone().or(defaults.executor),
+                    "depends_on": target.depends_on.clone().or(defaults.depends_on).unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        let deployable = project
+            .project_json
+            .targets
+            .iter()
+            .any(|(name, target)| is_container_target(name, target));
+
+        let mut dockerfile_template = DockerfileTemplate::builtin("Dockerfile.nx.bake.tera")?;
+        dockerfile_template.context.insert("project", &project.name);
+        dockerfile_template.context.insert("targets", &targets);
+        dockerfile_template.context.insert("tags", &project.project_json.tags);
+
+        packages.push(NxPackageInfo {
+            name: project.name.clone(),
+            version: project
+                .version
+                .clone()
+                .or_else(|| root_json.as_ref().map(|json| json.version.clone()))
+                .unwrap_or_else(|| "0.0.0".to_string()),
+            path: project.path.clone(),
+            dependencies,
+            tags: project.project_json.tags.clone(),
+            deployable,
+            metadata: project.metadata.clone(),
+            dockerfile_template,
+        });
+    }
+
+    let package_names: Vec<_> = packages.iter().map(|p| p.name.as_str()).collect();
+    let root_package = NxPackageInfo {
+        name: root_json
+            .as_ref()
+            .map(|json| json.name.clone())
+            .unwrap_or_else(|| root_name(workspace_root, &package_names)),
+        version: root_json
+            .as_ref()
+            .map(|json| json.version.clone())
+            .unwrap_or_else(|| "0.0.0".to_string()),
+        path: workspace_root.to_path_buf(),
+        dependencies: HashSet::new(),
+        tags: vec![],
+        deployable: false,
+        metadata: None,
+        dockerfile_template: {
+            let mut template = DockerfileTemplate::builtin("Dockerfile.nx.root.tera")?;
+            template.context.insert(
+                "node_version",
+                &root_json
+                    .as_ref()
+                    .and_then(|json| json.engines.as_ref())
+                    .and_then(|engines| engines.node.clone())
+                    .unwrap_or_default(),
+            );
+            template
+                .context
+                .insert("package_manager", package_manager(workspace_root));
+            template
+        },
+    };
+
+    Ok(NxWorkspaceInfo {
+        root_package,
+        packages,
+    })
+}
+
+fn discover_projects(workspace_root: &Path) -> Result<Vec<NxProject>> {
+    let mut projects = Vec::new();
+
+    for entry in WalkDir::new(workspace_root)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || (!is_hidden(e) && e.file_name() != "node_modules"))
+    {
+        let entry = entry?;
+        if entry.file_name() != "project.json" {
+            continue;
+        }
+
+        let project_dir = entry.path().parent().unwrap();
+        let project_json: ProjectJson = serde_json::from_str(
+            &std::fs::read_to_string(entry.path())
+                .with_context(|| format!("Failed to read {}", entry.path().display()))?,
+        )
+        .with_context(|| format!("Failed to parse {}", entry.path().display()))?;
+
+        let package_json = load_package_json(&project_dir.join("package.json")).ok();
+        let mut npm_dependencies = HashSet::new();
+        if let Some(package_json) = &package_json {
+            for deps in [&package_json.dependencies, &package_json.dev_dependencies]
+                .into_iter()
+                .flatten()
+            {
+                npm_dependencies.extend(deps.keys().cloned());
+            }
+        }
+
+        let name = project_json
+            .name
+            .clone()
+            .or_else(|| package_json.as_ref().map(|json| json.name.clone()))
+            .unwrap_or_else(|| project_dir.file_name().unwrap().to_string_lossy().to_string());
+
+        projects.push(NxProject {
+            name,
+            path: project_dir.to_path_buf(),
+            project_json,
+            package_json_name: package_json.as_ref().map(|json| json.name.clone()),
+            metadata: package_json.as_ref().map(|json| json.metadata()),
+            version: package_json.map(|json| json.version),
+            npm_dependencies,
+        });
+    }
+
+    projects.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(projects)
+}
+
+/// Targets such as `docker-build`, `container` or anything run by a Docker/container
+/// executor (`@nx-tools/nx-container:build`) mark a project as deployable
+fn is_container_target(name: &str, target: &NxTarget) -> bool {
+    let mentions_container = |s: &str| s.contains("docker") || s.contains("container");
+    mentions_container(name) || target.executor.as_deref().is_some_and(mentions_container)
+}
+
+fn package_manager(workspace_root: &Path) -> &'static str {
+    if workspace_root.join("pnpm-lock.yaml").is_file() {
+        "pnpm"
+    } else if workspace_root.join("yarn.lock").is_file() {
+        "yarn"
+    } else {
+        "npm"
+    }
+}
+
+fn load_nx_json(path: &Path) -> Result<NxJson> {
+    let content = std::fs::read_to_string(path).context("Failed to read nx.json")?;
+
+    serde_json::from_str(&content).context("Failed to parse nx.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_workspace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+
+        fs::write(root.join("nx.json"), r#"{"targetDefaults": {"build": {"dependsOn": ["^build"]}}}"#)?;
+        fs::write(root.join("package.json"), r#"{"name": "acme", "version": "3.0.0"}"#)?;
+        for dir in ["apps/shop", "libs/ui", "libs/utils"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        fs::write(
+            root.join("apps/shop/project.json"),
+            r#"{
+                "name": "shop",
+                "tags": ["scope:shop", "type:app"],
+                "implicitDependencies": ["utils"],
+                "targets": {
+                    "build": {"executor": "@nx/webpack:webpack"},
+                    "docker-build": {"executor": "nx:run-commands"}
+                }
+            }"#,
+        )?;
+        fs::write(root.join("apps/shop/package.json"), r#"{"name": "@acme/shop", "version": "1.2.0", "dependencies": {"@acme/ui": "*"}}"#)?;
+        fs::write(root.join("libs/ui/project.json"), r#"{"tags": ["scope:shared"]}"#)?;
+        fs::write(
+            root.join("libs/ui/package.json"),
+            r#"{"name": "@acme/ui", "version": "0.1.0", "license": "MIT", "description": "Shared UI"}"#,
+        )?;
+        fs::write(root.join("libs/utils/project.json"), r#"{"name": "utils"}"#)?;
+
+        let workspace = crate::workspace::Workspace::new(load_workspace(root)?);
+        assert_eq!(workspace.name, "acme");
+
+        let shop = &workspace.packages["shop"];
+        assert_eq!(shop.version, "1.2.0");
+        assert!(shop.deployable);
+        assert_eq!(shop.tags, vec!["scope:shop", "type:app"]);
+        let mut deps: Vec<_> = shop.dependencies.keys().cloned().collect();
+        deps.sort();
+        assert_eq!(deps, vec!["acme", "acme-ui", "utils"]);
+
+        let ui = &workspace.packages["acme-ui"];
+        assert!(!ui.deployable);
+        assert_eq!(ui.version, "0.1.0");
+        assert_eq!(ui.metadata.license.as_deref(), Some("MIT"));
+        assert_eq!(ui.metadata.description.as_deref(), Some("Shared UI"));
+        assert_eq!(workspace.packages["utils"].metadata, PackageMetadata::default());
+        assert_eq!(workspace.packages["utils"].version, "3.0.0");
+
+        let dockerfile = shop.dockerfile_template.render()?;
+        assert!(dockerfile.contains("RUN npx nx run shop:build"));
+
+        Ok(())
+    }
+}
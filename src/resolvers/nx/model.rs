@@ -0,0 +1,31 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NxJson {
+    /// Defaults applied to every project target with the same name
+    #[serde(default)]
+    pub target_defaults: HashMap<String, NxTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectJson {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Project names, `"*"` for every project, or `"!name"` to drop an inferred dependency
+    #[serde(default)]
+    pub implicit_dependencies: Vec<String>,
+    #[serde(default)]
+    pub targets: HashMap<String, NxTarget>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NxTarget {
+    pub executor: Option<String>,
+    /// Either target names or `{ "target": ..., "projects": ... }` objects, kept as-is
+    pub depends_on: Option<Vec<serde_json::Value>>,
+}
@@ -1,24 +1,29 @@
 use crate::{
+    config::PackageSettings,
     dockerfile::DockerfileTemplate,
-    workspace::{PackageInfo, WorkspaceInfo},
+    workspace::{PackageInfo, PackageMetadata, WorkspaceInfo},
 };
 use anyhow::{Context, Result};
 use std::collections::HashSet;
-use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 pub mod model;
+pub mod tsconfig;
+pub mod turbo;
 pub use model::*; // Change from pub use to private use
+use tsconfig::ReferenceMode;
+use turbo::Turbo;
 
 #[derive(Debug, Clone)]
-struct PnpmPackageInfo {
+pub struct PnpmPackageInfo {
     name: String,
     version: String,
     path: PathBuf,
     dependencies: HashSet<String>,
-    engines: Option<Engines>,
+    outputs: Vec<String>,
+    references: Vec<(String, Option<String>)>,
+    settings: Option<PackageSettings>,
+    metadata: Option<PackageMetadata>,
     dockerfile_template: DockerfileTemplate,
 }
 
@@ -42,11 +47,27 @@ impl PackageInfo for PnpmPackageInfo {
     fn dockerfile_template(&self) -> &DockerfileTemplate {
         &self.dockerfile_template
     }
+
+    fn settings(&self) -> Option<&PackageSettings> {
+        self.settings.as_ref()
+    }
+
+    fn metadata(&self) -> Option<&PackageMetadata> {
+        self.metadata.as_ref()
+    }
+
+    fn outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    fn references(&self) -> &[(String, Option<String>)] {
+        &self.references
+    }
 }
 
 #[derive(Debug)]
 pub struct PnpmWorkspaceInfo {
-    pub root_package: PnpmPackageInfo,
+    root_package: PnpmPackageInfo,
     packages: Vec<PnpmPackageInfo>,
 }
 
@@ -63,7 +84,7 @@ impl WorkspaceInfo for PnpmWorkspaceInfo {
     }
 }
 
-pub fn load_workspace(workspace_root: &Path) -> Result<PnpmWorkspaceInfo> {
+pub fn load_workspace(workspace_root: &Path, references: ReferenceMode) -> Result<PnpmWorkspaceInfo> {
     // Load root package.json
     let root_json = load_package_json(&workspace_root.join("package.json"))?;
 
@@ -75,13 +96,12 @@ pub fn load_workspace(workspace_root: &Path) -> Result<PnpmWorkspaceInfo> {
         version: root_json.version,
         path: workspace_root.to_path_buf(),
         dependencies: HashSet::new(),
-        engines: root_json.engines,
+        outputs: vec![],
+        references: vec![],
+        settings: None,
+        metadata: None,
         dockerfile_template: {
-            let mut template = DockerfileTemplate::new(&PathBuf::from(concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/src/templates/Dockerfile.root.tera"
-            )))
-            .unwrap();
+            let mut template = DockerfileTemplate::builtin("Dockerfile.root.tera").unwrap();
             template.context.insert(
                 "node_version",
                 &root_json_clone
@@ -103,8 +123,15 @@ pub fn load_workspace(workspace_root: &Path) -> Result<PnpmWorkspaceInfo> {
         println!("- {}", package_glob);
     }
 
+    // turbo.json, when present, decides which scripts each package runs
+    let turbo = Turbo::load(workspace_root)?;
+
     // Discover all packages
-    let packages = discover_workspace_packages(workspace_root, &workspace_config.packages)?;
+    let mut packages =
+        discover_workspace_packages(workspace_root, &workspace_config.packages, turbo.as_ref())?;
+
+    // Optionally check tsconfig.json project references against package.json
+    tsconfig::apply_references(&mut packages, references)?;
 
     Ok(PnpmWorkspaceInfo {
         root_package,
@@ -115,6 +142,7 @@ pub fn load_workspace(workspace_root: &Path) -> Result<PnpmWorkspaceInfo> {
 fn discover_workspace_packages(
     workspace_root: &Path,
     package_globs: &[String],
+    turbo: Option<&Turbo>,
 ) -> Result<Vec<PnpmPackageInfo>> {
     let mut packages = Vec::new();
 
@@ -124,7 +152,7 @@ fn discover_workspace_packages(
     for entry in WalkDir::new(workspace_root)
         .follow_links(true)
         .into_iter()
-        .filter_entry(|e| !is_hidden(e))
+        .filter_entry(|e| e.depth() == 0 || !is_hidden(e))
     {
         let entry = entry?;
         if entry.file_name() == "package.json" {
@@ -163,17 +191,33 @@ fn discover_workspace_packages(
                 dependencies.extend(dev_deps.keys().cloned());
             }
 
+            let tasks = match turbo {
+                Some(turbo) => turbo.task_chain(
+                    &package_json.name,
+                    package_dir,
+                    package_json.scripts.as_ref().unwrap_or(&Default::default()),
+                )?,
+                None => vec![],
+            };
+            let mut outputs: Vec<_> = tasks.iter().flat_map(|task| task.outputs.clone()).collect();
+            outputs.sort();
+            outputs.dedup();
+
+            let mut dockerfile_template = DockerfileTemplate::builtin("Dockerfile.bake.tera").unwrap();
+            dockerfile_template.context.insert("package_name", &package_json.name);
+            dockerfile_template.context.insert("tasks", &tasks);
+
+            let metadata = package_json.metadata();
             packages.push(PnpmPackageInfo {
                 name: package_json.name,
                 version: package_json.version,
                 path: package_dir.to_path_buf(),
                 dependencies,
-                engines: package_json.engines,
-                dockerfile_template: DockerfileTemplate::new(&PathBuf::from(concat!(
-                    env!("CARGO_MANIFEST_DIR"),
-                    "/src/templates/Dockerfile.bake.tera"
-                )))
-                .unwrap(),
+                outputs,
+                references: vec![],
+                settings: package_json.bakehouse,
+                metadata: Some(metadata),
+                dockerfile_template,
             });
         }
     }
@@ -181,7 +225,7 @@ fn discover_workspace_packages(
     Ok(packages)
 }
 
-fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+pub(crate) fn is_hidden(entry: &walkdir::DirEntry) -> bool {
     entry
         .file_name()
         .to_str()
@@ -189,7 +233,7 @@ fn is_hidden(entry: &walkdir::DirEntry) -> bool {
         .unwrap_or(false)
 }
 
-fn load_package_json(path: &Path) -> Result<PackageJson> {
+pub(crate) fn load_package_json(path: &Path) -> Result<PackageJson> {
     let content = std::fs::read_to_string(path).context("Failed to read package.json")?;
 
     serde_json::from_str(&content).context("Failed to parse package.json")
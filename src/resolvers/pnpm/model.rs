@@ -1,4 +1,7 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::config::PackageSettings;
+use crate::workspace::PackageMetadata;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PnpmWorkspace {
@@ -9,10 +12,113 @@ pub struct PnpmWorkspace {
 pub struct PackageJson {
     pub name: String,
     pub version: String,
+    pub scripts: Option<std::collections::HashMap<String, String>>,
     pub dependencies: Option<std::collections::HashMap<String, String>>,
     #[serde(rename = "devDependencies")]
     pub dev_dependencies: Option<std::collections::HashMap<String, String>>,
     pub engines: Option<Engines>,
+    /// Per-package bakehouse settings
+    pub bakehouse: Option<PackageSettings>,
+    // Only used for labels, so forms that aren't understood are left out rather than failing
+    #[serde(default, deserialize_with = "lenient")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub license: Option<License>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub repository: Option<Repository>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub homepage: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub author: Option<Person>,
+}
+
+/// Deserialize a field, or `None` when it has an unexpected shape
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).ok())
+}
+
+impl PackageJson {
+    /// What image labels are made from
+    pub fn metadata(&self) -> PackageMetadata {
+        PackageMetadata {
+            description: self.description.clone(),
+            license: self.license.as_ref().map(License::spdx),
+            repository: self.repository.as_ref().map(Repository::url),
+            homepage: self.homepage.clone(),
+            author: self.author.as_ref().map(Person::to_string),
+        }
+    }
+}
+
+/// `"MIT"`, or the deprecated `{ "type": "MIT", "url": "..." }`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum License {
+    Spdx(String),
+    Detailed {
+        #[serde(rename = "type")]
+        kind: String,
+    },
+}
+
+impl License {
+    pub fn spdx(&self) -> String {
+        match self {
+            License::Spdx(license) | License::Detailed { kind: license } => license.clone(),
+        }
+    }
+}
+
+/// `"github:acme/repo"`, a git URL or `{ "type": "git", "url": "...", "directory": "..." }`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Repository {
+    Url(String),
+    Detailed { url: String },
+}
+
+impl Repository {
+    /// A browsable URL, expanding npm's `github:`/`gitlab:`/`bitbucket:` shorthands
+    pub fn url(&self) -> String {
+        let url = match self {
+            Repository::Url(url) | Repository::Detailed { url } => url,
+        };
+        let hosts = [("github:", "github.com"), ("gitlab:", "gitlab.com"), ("bitbucket:", "bitbucket.org")];
+        if let Some((prefix, host)) = hosts.iter().find(|(prefix, _)| url.starts_with(prefix)) {
+            return format!("https://{}/{}", host, &url[prefix.len()..]);
+        }
+        if !url.contains(':') && url.split('/').count() == 2 {
+            return format!("https://github.com/{}", url);
+        }
+
+        url.trim_start_matches("git+")
+            .replace("git://", "https://")
+            .trim_end_matches(".git")
+            .to_string()
+    }
+}
+
+/// `"Jane Doe <jane@example.com> (https://example.com)"` or `{ "name": ..., "email": ..., "url": ... }`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Person {
+    Text(String),
+    Detailed { name: String, email: Option<String> },
+}
+
+impl std::fmt::Display for Person {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Person::Text(text) => write!(f, "{}", text),
+            Person::Detailed { name, email: Some(email) } => write!(f, "{} <{}>", name, email),
+            Person::Detailed { name, email: None } => write!(f, "{}", name),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,16 +126,42 @@ pub struct Engines {
     pub node: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Recipe {
-    pub name: String,
-    pub ingredients: Vec<Ingredient>,
-    pub preparation_time: u32, // in minutes
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Ingredient {
-    pub name: String,
-    pub quantity: f32,
-    pub unit: String,
+    #[test]
+    fn test_metadata_forms() {
+        let package_json: PackageJson = serde_json::from_str(
+            r#"{
+                "name": "@acme/web",
+                "version": "1.0.0",
+                "license": { "type": "MIT", "url": "https://opensource.org/licenses/MIT" },
+                "repository": { "type": "git", "directory": "apps/web" },
+                "author": { "email": "jane@example.com" },
+                "homepage": ["https://example.com"]
+            }"#,
+        )
+        .unwrap();
+        let metadata = package_json.metadata();
+        assert_eq!(metadata.license.as_deref(), Some("MIT"));
+        assert_eq!(metadata.repository, None);
+        assert_eq!(metadata.author, None);
+        assert_eq!(metadata.homepage, None);
+
+        let package_json: PackageJson = serde_json::from_str(
+            r#"{
+                "name": "@acme/web",
+                "version": "1.0.0",
+                "license": "Apache-2.0",
+                "repository": "github:acme/repo",
+                "author": { "name": "Jane Doe", "email": "jane@example.com" }
+            }"#,
+        )
+        .unwrap();
+        let metadata = package_json.metadata();
+        assert_eq!(metadata.license.as_deref(), Some("Apache-2.0"));
+        assert_eq!(metadata.repository.as_deref(), Some("https://github.com/acme/repo"));
+        assert_eq!(metadata.author.as_deref(), Some("Jane Doe <jane@example.com>"));
+    }
 }
@@ -0,0 +1,311 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::PnpmPackageInfo;
+use crate::jsonc;
+use crate::resolvers::normalize;
+
+/// What to do when `tsconfig.json` references and `package.json` dependencies disagree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceMode {
+    /// Don't read tsconfig files at all
+    #[default]
+    Off,
+    /// Print a warning for every mismatch
+    Report,
+    /// Print a warning for every mismatch and add referenced packages as dependencies
+    Add,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TsConfigJson {
+    /// A single path, or an array of them since TypeScript 5.0
+    extends: Option<Extends>,
+    #[serde(default)]
+    compiler_options: serde_json::Map<String, serde_json::Value>,
+    references: Option<Vec<Reference>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Extends {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct Reference {
+    path: PathBuf,
+}
+
+/// A package's tsconfig with its `extends` chain applied
+#[derive(Debug, Default)]
+pub struct TsConfig {
+    pub compiler_options: serde_json::Map<String, serde_json::Value>,
+    /// `outDir`, resolved against the file that sets it as TypeScript does
+    pub out_dir: Option<PathBuf>,
+    /// Directories of the referenced projects. As in TypeScript, references aren't inherited
+    pub references: Vec<PathBuf>,
+}
+
+impl TsConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::load_chain(path, &mut HashSet::new())
+    }
+
+    fn load_chain(path: &Path, seen: &mut HashSet<PathBuf>) -> Result<Self> {
+        if !seen.insert(path.to_path_buf()) {
+            bail!("Circular `extends` in {}", path.display());
+        }
+
+        let json: TsConfigJson = jsonc::from_str(
+            &std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?,
+        )
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        // Later entries in `extends` win, and the file itself wins over all of them
+        let mut compiler_options = serde_json::Map::new();
+        let mut out_dir = None;
+        let extends = match json.extends {
+            Some(Extends::One(base)) => vec![base],
+            Some(Extends::Many(bases)) => bases,
+            None => vec![],
+        };
+        for base in extends {
+            if let Some(base_path) = resolve_extends(dir, &base) {
+                let base = Self::load_chain(&base_path, seen)?;
+                compiler_options.extend(base.compiler_options);
+                out_dir = base.out_dir.or(out_dir);
+            }
+        }
+        if let Some(own) = json.compiler_options.get("outDir").and_then(|out_dir| out_dir.as_str()) {
+            out_dir = Some(normalize(&dir.join(own)));
+        }
+        compiler_options.extend(json.compiler_options);
+
+        let references = json
+            .references
+            .unwrap_or_default()
+            .into_iter()
+            .map(|reference| {
+                let path = dir.join(reference.path);
+                // A reference may name the project directory or its tsconfig file
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    path.parent().map(Path::to_path_buf).unwrap_or(path)
+                } else {
+                    path
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            compiler_options,
+            out_dir,
+            references,
+        })
+    }
+}
+
+/// Find the file an `extends` entry points at: a relative path, or a package in `node_modules`.
+/// Bases that aren't installed are skipped rather than failing the whole run.
+fn resolve_extends(dir: &Path, base: &str) -> Option<PathBuf> {
+    let with_extension = |path: PathBuf| {
+        if path.is_file() {
+            Some(path)
+        } else {
+            let json = path.with_extension("json");
+            json.is_file().then_some(json)
+        }
+    };
+
+    if base.starts_with('.') || Path::new(base).is_absolute() {
+        return with_extension(dir.join(base));
+    }
+
+    dir.ancestors()
+        .map(|ancestor| ancestor.join("node_modules").join(base))
+        .find_map(|candidate| {
+            with_extension(candidate.clone()).or_else(|| {
+                let tsconfig = candidate.join("tsconfig.json");
+                tsconfig.is_file().then_some(tsconfig)
+            })
+        })
+}
+
+/// Compare every package's tsconfig references with its package.json dependencies, report
+/// the mismatches and, in [`ReferenceMode::Add`], add the missing dependency edges.
+/// Referenced projects that end up as dependencies are kept as the package's `references`.
+pub fn apply_references(packages: &mut [PnpmPackageInfo], mode: ReferenceMode) -> Result<()> {
+    if mode == ReferenceMode::Off {
+        return Ok(());
+    }
+
+    let by_path: HashMap<PathBuf, String> = packages
+        .iter()
+        .map(|package| (normalize(&package.path), package.name.clone()))
+        .collect();
+
+    let mut tsconfigs = HashMap::new();
+    for package in packages.iter() {
+        let tsconfig_path = package.path.join("tsconfig.json");
+        if tsconfig_path.is_file() {
+            tsconfigs.insert(package.name.clone(), TsConfig::load(&tsconfig_path)?);
+        }
+    }
+
+    for package in packages.iter_mut() {
+        let Some(tsconfig) = tsconfigs.get(&package.name) else {
+            continue;
+        };
+
+        let mut referenced = Vec::new();
+        for reference in &tsconfig.references {
+            let path = normalize(reference);
+            match by_path.get(&path) {
+                Some(name) => referenced.push((name.clone(), path)),
+                None => println!(
+                    "Warning: {} references {}, which isn't a workspace package",
+                    package.name,
+                    reference.display()
+                ),
+            }
+        }
+
+        for (name, _) in &referenced {
+            if !package.dependencies.contains(name) {
+                println!(
+                    "Warning: {} references {} in tsconfig.json but doesn't depend on it in package.json{}",
+                    package.name,
+                    name,
+                    if mode == ReferenceMode::Add { " (adding it)" } else { "" }
+                );
+            }
+        }
+        let mut unreferenced: Vec<_> = package
+            .dependencies
+            .iter()
+            .filter(|dep| by_path.values().any(|name| name == *dep))
+            .filter(|dep| !referenced.iter().any(|(name, _)| name == *dep))
+            .collect();
+        unreferenced.sort();
+        for dep in unreferenced {
+            println!(
+                "Warning: {} depends on {} in package.json but doesn't reference it in tsconfig.json",
+                package.name, dep
+            );
+        }
+
+        if mode == ReferenceMode::Add {
+            package
+                .dependencies
+                .extend(referenced.iter().map(|(name, _)| name.clone()));
+        }
+
+        // An outDir outside the referenced package isn't something it can be copied from
+        package.references = referenced
+            .iter()
+            .filter(|(name, _)| package.dependencies.contains(name))
+            .map(|(name, path)| {
+                let out_dir = tsconfigs
+                    .get(name)
+                    .and_then(|tsconfig| tsconfig.out_dir.as_ref())
+                    .and_then(|out_dir| out_dir.strip_prefix(path).ok())
+                    .filter(|out_dir| !out_dir.as_os_str().is_empty())
+                    .map(|out_dir| out_dir.to_string_lossy().into_owned());
+                (name.clone(), out_dir)
+            })
+            .collect();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolvers::pnpm::load_workspace;
+    use crate::workspace::Workspace;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace() -> Result<TempDir> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+
+        fs::write(root.join("package.json"), r#"{"name": "mono", "version": "1.0.0"}"#)?;
+        fs::write(root.join("pnpm-workspace.yaml"), "packages:\n  - 'packages/*'\n")?;
+        fs::write(
+            root.join("tsconfig.base.json"),
+            r#"{
+                // Shared options
+                "compilerOptions": {"composite": true, "outDir": "./lib",},
+            }"#,
+        )?;
+
+        for (name, package_json, tsconfig) in [
+            (
+                "app",
+                r#"{"name": "@mono/app", "version": "1.0.0", "dependencies": {"@mono/util": "workspace:*"}}"#,
+                r#"{"extends": "../../tsconfig.base.json", "references": [{"path": "../core"}]}"#,
+            ),
+            (
+                "core",
+                r#"{"name": "@mono/core", "version": "1.0.0"}"#,
+                r#"{"extends": ["../../tsconfig.base.json"], "compilerOptions": {"outDir": "build"}}"#,
+            ),
+            ("util", r#"{"name": "@mono/util", "version": "1.0.0"}"#, r#"{}"#),
+        ] {
+            let dir = root.join("packages").join(name);
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join("package.json"), package_json)?;
+            fs::write(dir.join("tsconfig.json"), tsconfig)?;
+        }
+
+        Ok(temp_dir)
+    }
+
+    #[test]
+    fn test_load_follows_extends() -> Result<()> {
+        let temp_dir = workspace()?;
+
+        let root = normalize(temp_dir.path());
+
+        // An inherited outDir is relative to the base that sets it, not to the package
+        let app = TsConfig::load(&temp_dir.path().join("packages/app/tsconfig.json"))?;
+        assert_eq!(app.compiler_options["composite"], true);
+        assert_eq!(app.out_dir, Some(root.join("lib")));
+        assert_eq!(app.references.len(), 1);
+
+        let core = TsConfig::load(&temp_dir.path().join("packages/core/tsconfig.json"))?;
+        assert_eq!(core.compiler_options["composite"], true);
+        assert_eq!(core.out_dir, Some(root.join("packages/core/build")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_references() -> Result<()> {
+        let temp_dir = workspace()?;
+
+        let reported = Workspace::new(load_workspace(temp_dir.path(), ReferenceMode::Report)?);
+        assert!(!reported.packages["mono-app"].dependencies.contains_key("mono-core"));
+
+        let added = Workspace::new(load_workspace(temp_dir.path(), ReferenceMode::Add)?);
+        let app = &added.packages["mono-app"];
+        assert!(app.dependencies.contains_key("mono-core"));
+        assert!(app.dependencies.contains_key("mono-util"));
+
+        let dockerfile = app.dockerfile_template.render()?;
+        assert!(dockerfile.contains(
+            "COPY --from=mono-core /app/packages/core/tsconfig.json /app/packages/core/tsconfig.json"
+        ));
+        assert!(dockerfile.contains("COPY --from=mono-core /app/packages/core/build /app/packages/core/build"));
+
+        Ok(())
+    }
+}
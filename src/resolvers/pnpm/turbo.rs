@@ -0,0 +1,247 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::jsonc;
+
+/// The task a package image is built for; everything it depends on runs before it
+pub const ENTRY_TASK: &str = "build";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TurboJson {
+    /// `pipeline` in turbo 1.x
+    #[serde(default, alias = "pipeline")]
+    pub tasks: HashMap<String, TurboTask>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurboTask {
+    pub depends_on: Option<Vec<String>>,
+    pub outputs: Option<Vec<String>>,
+    pub inputs: Option<Vec<String>>,
+}
+
+/// One step of a package's task chain, as exposed to templates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskStep {
+    pub name: String,
+    /// Directories (relative to the package) the task produces
+    pub outputs: Vec<String>,
+    pub inputs: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Turbo {
+    root: TurboJson,
+}
+
+impl Turbo {
+    /// Load the root `turbo.json`, if the workspace has one
+    pub fn load(workspace_root: &Path) -> Result<Option<Self>> {
+        let path = workspace_root.join("turbo.json");
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            root: load_turbo_json(&path)?,
+        }))
+    }
+
+    /// The tasks to run inside a package's image, in order, ending with [`ENTRY_TASK`].
+    ///
+    /// `^task` dependencies are left out as they run in the dependency's own image, and tasks
+    /// the package has no script for are skipped, as turbo does.
+    pub fn task_chain(
+        &self,
+        package_name: &str,
+        package_dir: &Path,
+        scripts: &HashMap<String, String>,
+    ) -> Result<Vec<TaskStep>> {
+        // A package-level turbo.json (`"extends": ["//"]`) overrides individual task fields
+        let package_turbo = match package_dir.join("turbo.json") {
+            path if path.is_file() => load_turbo_json(&path)?,
+            _ => TurboJson::default(),
+        };
+
+        let resolver = TaskResolver {
+            root: &self.root,
+            package: &package_turbo,
+            package_name,
+            scripts,
+        };
+
+        let mut chain = Vec::new();
+        resolver.visit(ENTRY_TASK, &mut HashSet::new(), &mut chain)?;
+
+        Ok(chain)
+    }
+}
+
+struct TaskResolver<'a> {
+    root: &'a TurboJson,
+    package: &'a TurboJson,
+    package_name: &'a str,
+    scripts: &'a HashMap<String, String>,
+}
+
+impl TaskResolver<'_> {
+    fn task(&self, name: &str) -> TurboTask {
+        let root = self
+            .root
+            .tasks
+            .get(&format!("{}#{}", self.package_name, name))
+            .or_else(|| self.root.tasks.get(name))
+            .cloned()
+            .unwrap_or_default();
+
+        match self.package.tasks.get(name) {
+            Some(package) => TurboTask {
+                depends_on: package.depends_on.clone().or(root.depends_on),
+                outputs: package.outputs.clone().or(root.outputs),
+                inputs: package.inputs.clone().or(root.inputs),
+            },
+            None => root,
+        }
+    }
+
+    fn visit(&self, name: &str, visiting: &mut HashSet<String>, chain: &mut Vec<TaskStep>) -> Result<()> {
+        if chain.iter().any(|step| step.name == name) {
+            return Ok(());
+        }
+        if !visiting.insert(name.to_string()) {
+            bail!("Cycle in turbo.json tasks for {} at '{}'", self.package_name, name);
+        }
+
+        let task = self.task(name);
+        for dependency in task.depends_on.iter().flatten() {
+            if dependency.starts_with('^') {
+                continue;
+            }
+            match dependency.split_once('#') {
+                Some((package, task)) if package == self.package_name => self.visit(task, visiting, chain)?,
+                Some(_) => {}
+                None => self.visit(dependency, visiting, chain)?,
+            }
+        }
+
+        visiting.remove(name);
+
+        if self.scripts.contains_key(name) {
+            chain.push(TaskStep {
+                name: name.to_string(),
+                outputs: task
+                    .outputs
+                    .iter()
+                    .flatten()
+                    .filter_map(|o| output_dir(o))
+                    .fold(Vec::new(), |mut outputs, dir| {
+                        if !outputs.contains(&dir) {
+                            outputs.push(dir);
+                        }
+                        outputs
+                    }),
+                inputs: task.inputs.unwrap_or_default(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Turn an output glob such as `dist/**/*.js` into the directory to copy, the path up to its
+/// first wildcard. Exclusions (`!.next/cache/**`) have nothing to copy.
+fn output_dir(output: &str) -> Option<String> {
+    if output.starts_with('!') {
+        return None;
+    }
+
+    let dir: Vec<_> = output
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .take_while(|segment| !segment.contains(['*', '?', '[', '{']))
+        .collect();
+
+    (!dir.is_empty()).then(|| dir.join("/"))
+}
+
+fn load_turbo_json(path: &Path) -> Result<TurboJson> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    jsonc::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_task_chain() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::write(
+            root.join("turbo.json"),
+            r#"{
+                // Comments are allowed
+                "tasks": {
+                    "build": {"dependsOn": ["^build", "codegen", "lint"], "outputs": ["dist/**", "dist/**/*.js", "!dist/cache/**"]},
+                    "codegen": {"outputs": ["src/generated/**"], "inputs": ["schema.graphql"]},
+                    "lint": {},
+                    "@acme/web#build": {"dependsOn": ["^build", "codegen"], "outputs": [".next/**"]}
+                }
+            }"#,
+        )?;
+        fs::create_dir_all(root.join("api"))?;
+        fs::write(root.join("api/turbo.json"), r#"{"extends": ["//"], "tasks": {"build": {"outputs": ["out/**"]}}}"#)?;
+
+        let turbo = Turbo::load(root)?.unwrap();
+        let scripts: HashMap<_, _> = ["build", "codegen"]
+            .iter()
+            .map(|s| (s.to_string(), String::new()))
+            .collect();
+
+        let chain = turbo.task_chain("@acme/lib", &root.join("lib"), &scripts)?;
+        assert_eq!(
+            chain,
+            vec![
+                TaskStep {
+                    name: "codegen".to_string(),
+                    outputs: vec!["src/generated".to_string()],
+                    inputs: vec!["schema.graphql".to_string()],
+                },
+                TaskStep {
+                    name: "build".to_string(),
+                    outputs: vec!["dist".to_string()],
+                    inputs: vec![],
+                },
+            ]
+        );
+
+        let web = turbo.task_chain("@acme/web", &root.join("web"), &scripts)?;
+        assert_eq!(web[1].outputs, vec![".next".to_string()]);
+
+        // Package-level turbo.json overrides outputs but keeps the root's dependsOn
+        let api = turbo.task_chain("@acme/api", &root.join("api"), &scripts)?;
+        assert_eq!(api.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["codegen", "build"]);
+        assert_eq!(api[1].outputs, vec!["out".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_dir() {
+        assert_eq!(output_dir("dist/**").as_deref(), Some("dist"));
+        assert_eq!(output_dir("dist/**/*.js").as_deref(), Some("dist"));
+        assert_eq!(output_dir("./build/types/*.d.ts").as_deref(), Some("build/types"));
+        assert_eq!(output_dir("storybook-static/").as_deref(), Some("storybook-static"));
+        assert_eq!(output_dir("lib/{cjs,esm}/**").as_deref(), Some("lib"));
+        assert_eq!(output_dir("!dist/cache/**"), None);
+        assert_eq!(output_dir("*.tsbuildinfo"), None);
+        assert_eq!(output_dir("**"), None);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    config::PackageSettings,
+    dockerfile::DockerfileTemplate,
+    jsonc,
+    workspace::{PackageInfo, PackageMetadata, WorkspaceInfo},
+};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+pub mod model;
+use model::*;
+
+use super::pnpm::load_package_json;
+use super::{normalize, root_name};
+
+/// Where Rush keeps the shared pnpm lockfile, and the folder its importer paths are relative to
+const LOCKFILE: &str = "common/config/rush/pnpm-lock.yaml";
+const LOCKFILE_BASE: &str = "common/temp";
+
+#[derive(Debug, Clone)]
+pub struct RushPackageInfo {
+    name: String,
+    version: String,
+    path: PathBuf,
+    dependencies: HashSet<String>,
+    settings: Option<PackageSettings>,
+    metadata: Option<PackageMetadata>,
+    dockerfile_template: DockerfileTemplate,
+}
+
+impl PackageInfo for RushPackageInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn dependencies(&self) -> &HashSet<String> {
+        &self.dependencies
+    }
+
+    fn dockerfile_template(&self) -> &DockerfileTemplate {
+        &self.dockerfile_template
+    }
+
+    fn settings(&self) -> Option<&PackageSettings> {
+        self.settings.as_ref()
+    }
+
+    fn metadata(&self) -> Option<&PackageMetadata> {
+        self.metadata.as_ref()
+    }
+}
+
+#[derive(Debug)]
+pub struct RushWorkspaceInfo {
+    root_package: RushPackageInfo,
+    packages: Vec<RushPackageInfo>,
+}
+
+impl WorkspaceInfo for RushWorkspaceInfo {
+    fn root_package(&self) -> &dyn PackageInfo {
+        &self.root_package
+    }
+
+    fn packages(&self) -> Vec<&dyn PackageInfo> {
+        self.packages
+            .iter()
+            .map(|p| p as &dyn PackageInfo)
+            .collect()
+    }
+}
+
+pub fn load_workspace(workspace_root: &Path) -> Result<RushWorkspaceInfo> {
+    let rush_json_path = workspace_root.join("rush.json");
+    let rush_json: RushJson = jsonc::from_str(
+        &std::fs::read_to_string(&rush_json_path).context("Failed to read rush.json")?,
+    )
+    .context("Failed to parse rush.json")?;
+
+    let lock_links = load_lock_links(workspace_root)?;
+
+    let folders: HashMap<PathBuf, &str> = rush_json
+        .projects
+        .iter()
+        .map(|project| (normalize(&project.project_folder), project.package_name.as_str()))
+        .collect();
+    let project_names: HashSet<&str> = folders.values().copied().collect();
+
+    let mut packages = Vec::new();
+    for project in &rush_json.projects {
+        let project_dir = workspace_root.join(&project.project_folder);
+        let package_json = load_package_json(&project_dir.join("package.json"))?;
+
+        // Local projects are linked through the workspace protocol...
+        let mut dependencies: HashSet<String> = [&package_json.dependencies, &package_json.dev_dependencies]
+            .into_iter()
+            .flatten()
+            .flat_map(|deps| deps.iter())
+            .filter(|(name, spec)| spec.starts_with("workspace:") && project_names.contains(name.as_str()))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        // ...and show up as `link:` entries in the shared lockfile
+        if let Some(links) = lock_links.get(&normalize(&project.project_folder)) {
+            dependencies.extend(
+                links
+                    .iter()
+                    .filter_map(|folder| folders.get(folder))
+                    .map(|name| name.to_string()),
+            );
+        }
+
+        // Decoupled dependencies come from the registry, not the workspace
+        for decoupled in &project.decoupled_local_dependencies {
+            dependencies.remove(decoupled);
+        }
+
+        let mut dockerfile_template = DockerfileTemplate::builtin("Dockerfile.rush.bake.tera")?;
+        dockerfile_template
+            .context
+            .insert("package_name", &project.package_name);
+
+        let metadata = package_json.metadata();
+        packages.push(RushPackageInfo {
+            name: project.package_name.clone(),
+            version: package_json.version,
+            path: project_dir,
+            dependencies,
+            settings: package_json.bakehouse,
+            metadata: Some(metadata),
+            dockerfile_template,
+        });
+    }
+
+    let package_names: Vec<_> = packages.iter().map(|p| p.name.as_str()).collect();
+    let root_package = RushPackageInfo {
+        name: root_name(workspace_root, &package_names),
+        version: "0.0.0".to_string(),
+        path: workspace_root.to_path_buf(),
+        dependencies: HashSet::new(),
+        settings: None,
+        metadata: None,
+        dockerfile_template: {
+            let mut template = DockerfileTemplate::builtin("Dockerfile.rush.root.tera")?;
+            template.context.insert(
+                "node_version",
+                &rush_json
+                    .node_supported_version_range
+                    .as_deref()
+                    .map(node_major)
+                    .unwrap_or_default(),
+            );
+            template.context.insert(
+                "rush_version",
+                rush_json.rush_version.as_deref().unwrap_or("latest"),
+            );
+            template
+        },
+    };
+
+    Ok(RushWorkspaceInfo {
+        root_package,
+        packages,
+    })
+}
+
+/// Project folders each lockfile importer links to, keyed by the importer's project folder
+fn load_lock_links(workspace_root: &Path) -> Result<HashMap<PathBuf, Vec<PathBuf>>> {
+    let path = workspace_root.join(LOCKFILE);
+    if !path.is_file() {
+        return Ok(HashMap::new());
+    }
+
+    let lock: PnpmLock = serde_yaml::from_str(
+        &std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", LOCKFILE))?,
+    )
+    .with_context(|| format!("Failed to parse {}", LOCKFILE))?;
+
+    Ok(lock
+        .importers
+        .iter()
+        .map(|(importer, deps)| {
+            let folder = normalize(&Path::new(LOCKFILE_BASE).join(importer));
+            let links = deps
+                .dependencies
+                .values()
+                .chain(deps.dev_dependencies.values())
+                .filter_map(|dep| dep.version().strip_prefix("link:"))
+                .map(|link| normalize(&folder.join(link)))
+                .collect();
+            (folder, links)
+        })
+        .collect())
+}
+
+/// The lowest Node major version in a range such as `>=18.12.0 <19.0.0`
+fn node_major(range: &str) -> String {
+    range
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_workspace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+
+        fs::write(
+            root.join("rush.json"),
+            r#"{
+                // rush.json allows comments
+                "rushVersion": "5.112.0",
+                "pnpmVersion": "8.15.0",
+                "nodeSupportedVersionRange": ">=18.12.0 <19.0.0",
+                "projects": [
+                    {"packageName": "@acme/web", "projectFolder": "apps/web", "decoupledLocalDependencies": ["@acme/legacy"]},
+                    {"packageName": "@acme/ui", "projectFolder": "libs/ui"},
+                    {"packageName": "@acme/icons", "projectFolder": "libs/icons"},
+                    {"packageName": "@acme/legacy", "projectFolder": "libs/legacy"},
+                ]
+            }"#,
+        )?;
+        for (folder, json) in [
+            ("apps/web", r#"{"name": "@acme/web", "version": "1.0.0", "dependencies": {"@acme/ui": "workspace:*", "@acme/legacy": "workspace:^1.0.0", "react": "^18"}}"#),
+            ("libs/ui", r#"{"name": "@acme/ui", "version": "2.0.0", "dependencies": {"@acme/icons": "^1.0.0"}}"#),
+            ("libs/icons", r#"{"name": "@acme/icons", "version": "1.0.0"}"#),
+            ("libs/legacy", r#"{"name": "@acme/legacy", "version": "1.0.0"}"#),
+        ] {
+            fs::create_dir_all(root.join(folder))?;
+            fs::write(root.join(folder).join("package.json"), json)?;
+        }
+
+        fs::create_dir_all(root.join("common/config/rush"))?;
+        fs::write(
+            root.join(LOCKFILE),
+            r#"
+lockfileVersion: '6.0'
+importers:
+  ../../libs/ui:
+    dependencies:
+      '@acme/icons':
+        specifier: ^1.0.0
+        version: link:../icons
+"#,
+        )?;
+
+        let workspace = crate::workspace::Workspace::new(load_workspace(root)?);
+
+        let web_deps: HashSet<_> = workspace.packages["acme-web"].dependencies.keys().cloned().collect();
+        assert_eq!(web_deps, HashSet::from(["acme-ui".to_string(), workspace.name.clone()]));
+
+        assert!(workspace.packages["acme-ui"].dependencies.contains_key("acme-icons"));
+
+        let dockerfile = workspace.packages["acme-web"].dockerfile_template.render()?;
+        assert!(dockerfile.contains("rush install --to @acme/web"));
+        assert!(dockerfile.contains("rush build --to @acme/web"));
+
+        let root_dockerfile = workspace.dockerfile_template.render()?;
+        assert!(root_dockerfile.contains("ARG NODE_VERSION=18\n"));
+
+        Ok(())
+    }
+}
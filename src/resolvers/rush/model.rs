@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RushJson {
+    pub rush_version: Option<String>,
+    /// A semver range such as `>=18.12.0 <19.0.0`
+    pub node_supported_version_range: Option<String>,
+    #[serde(default)]
+    pub projects: Vec<RushProject>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RushProject {
+    pub package_name: String,
+    pub project_folder: PathBuf,
+    /// Local projects this one consumes from the registry instead of linking
+    #[serde(default, alias = "cyclicDependencyProjects")]
+    pub decoupled_local_dependencies: Vec<String>,
+}
+
+/// The `importers` section of `common/config/rush/pnpm-lock.yaml`
+#[derive(Debug, Default, Deserialize)]
+pub struct PnpmLock {
+    #[serde(default)]
+    pub importers: HashMap<String, LockImporter>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockImporter {
+    #[serde(default)]
+    pub dependencies: HashMap<String, LockDependency>,
+    #[serde(default)]
+    pub dev_dependencies: HashMap<String, LockDependency>,
+}
+
+/// Lockfile v5 stores the resolved version directly, v6+ nests it next to the specifier
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum LockDependency {
+    Version(String),
+    Detailed { version: String },
+}
+
+impl LockDependency {
+    pub fn version(&self) -> &str {
+        match self {
+            LockDependency::Version(version) => version,
+            LockDependency::Detailed { version } => version,
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+/// Write a file under `root`, creating its parent directories
+pub fn write(root: &Path, path: &str, content: &str) -> Result<()> {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(path, content)?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// A build secret, read from a file or an environment variable where bake runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecretConfig {
+    /// Name the Dockerfile mounts it by
+    pub id: String,
+
+    /// File holding the secret, e.g. `${env:HOME}/.npmrc`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+
+    /// Environment variable holding the secret. Defaults to the id when `src` is unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
+
+    /// Where build steps find it, `/run/secrets/<id>` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl SecretConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !is_id(&self.id) {
+            bail!("Invalid secret id '{}'", self.id);
+        }
+        if self.src.is_some() && self.env.is_some() {
+            bail!("Secret {} sets both `src` and `env`", self.id);
+        }
+        Ok(())
+    }
+
+    /// The bake `secret` entry
+    pub fn entry(&self) -> String {
+        match &self.src {
+            Some(src) => format!("type=file,id={},src={}", self.id, src),
+            None => format!("type=env,id={},env={}", self.id, self.env.as_deref().unwrap_or(&self.id)),
+        }
+    }
+
+    /// The `RUN --mount` flag exposing it to a build step
+    pub fn mount(&self) -> String {
+        match &self.target {
+            Some(target) => format!("--mount=type=secret,id={},target={}", self.id, target),
+            None => format!("--mount=type=secret,id={}", self.id),
+        }
+    }
+}
+
+/// The id of a bake `ssh` entry, which is `default` or `<id>=<socket or keys>`
+pub fn ssh_id(entry: &str) -> &str {
+    entry.split_once('=').map_or(entry, |(id, _)| id)
+}
+
+pub fn validate_ssh(entry: &str) -> Result<()> {
+    if !is_id(ssh_id(entry)) {
+        bail!("Invalid ssh entry '{}'", entry);
+    }
+    Ok(())
+}
+
+/// The `RUN --mount` flag forwarding an SSH agent or keys to a build step
+pub fn ssh_mount(entry: &str) -> String {
+    match ssh_id(entry) {
+        "default" => "--mount=type=ssh".to_string(),
+        id => format!("--mount=type=ssh,id={}", id),
+    }
+}
+
+/// Networking for `RUN` steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Default,
+    /// No network at all, e.g. for tests that must not reach out
+    None,
+    /// The host's network, which needs the `network.host` entitlement
+    Host,
+}
+
+impl Network {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Default => "default",
+            Network::None => "none",
+            Network::Host => "host",
+        }
+    }
+}
+
+/// Privileges a build may ask for, granted with `docker buildx bake --allow`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Entitlement {
+    #[serde(rename = "network.host")]
+    NetworkHost,
+    #[serde(rename = "security.insecure")]
+    SecurityInsecure,
+}
+
+impl Entitlement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Entitlement::NetworkHost => "network.host",
+            Entitlement::SecurityInsecure => "security.insecure",
+        }
+    }
+}
+
+/// Secret and SSH ids may only contain letters, digits, `-`, `_` and `.`
+fn is_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(yaml: &str) -> SecretConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_secret() {
+        let npmrc = secret("id: npmrc\nsrc: ${env:HOME}/.npmrc\ntarget: /root/.npmrc");
+        assert!(npmrc.validate().is_ok());
+        assert_eq!(npmrc.entry(), "type=file,id=npmrc,src=${env:HOME}/.npmrc");
+        assert_eq!(npmrc.mount(), "--mount=type=secret,id=npmrc,target=/root/.npmrc");
+
+        let token = secret("id: NPM_TOKEN");
+        assert_eq!(token.entry(), "type=env,id=NPM_TOKEN,env=NPM_TOKEN");
+        assert_eq!(token.mount(), "--mount=type=secret,id=NPM_TOKEN");
+
+        assert!(secret("id: npm token").validate().is_err());
+        assert!(secret("id: token\nsrc: token.txt\nenv: TOKEN").validate().is_err());
+    }
+
+    #[test]
+    fn test_ssh() {
+        assert_eq!(ssh_mount("default"), "--mount=type=ssh");
+        assert_eq!(ssh_mount("github=${env:HOME}/.ssh/id_ed25519"), "--mount=type=ssh,id=github");
+        assert!(validate_ssh("github=${env:SSH_AUTH_SOCK}").is_ok());
+        assert!(validate_ssh("=key").is_err());
+
+        let network: Network = serde_yaml::from_str("none").unwrap();
+        assert_eq!(network.name(), "none");
+        let entitlements: Vec<Entitlement> = serde_yaml::from_str("[network.host]").unwrap();
+        assert_eq!(entitlements[0].name(), "network.host");
+        assert!(serde_yaml::from_str::<Entitlement>("device").is_err());
+    }
+}
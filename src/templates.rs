@@ -0,0 +1,280 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::{specificity, BakehouseConfig};
+use crate::resolvers;
+use crate::workspace::sanitized_name;
+
+/// The built-in templates, compiled into the binary so it works without the source tree
+const BUILTIN: &[(&str, &str)] = &[
+    ("Dockerfile.bake.tera", include_str!("templates/Dockerfile.bake.tera")),
+    ("Dockerfile.root.tera", include_str!("templates/Dockerfile.root.tera")),
+    ("Dockerfile.nx.bake.tera", include_str!("templates/Dockerfile.nx.bake.tera")),
+    ("Dockerfile.nx.root.tera", include_str!("templates/Dockerfile.nx.root.tera")),
+    ("Dockerfile.rush.bake.tera", include_str!("templates/Dockerfile.rush.bake.tera")),
+    ("Dockerfile.rush.root.tera", include_str!("templates/Dockerfile.rush.root.tera")),
+    ("Dockerfile.cargo.bake.tera", include_str!("templates/Dockerfile.cargo.bake.tera")),
+    ("Dockerfile.cargo.root.tera", include_str!("templates/Dockerfile.cargo.root.tera")),
+    ("Dockerfile.go.bake.tera", include_str!("templates/Dockerfile.go.bake.tera")),
+    ("Dockerfile.go.root.tera", include_str!("templates/Dockerfile.go.root.tera")),
+];
+
+/// Key under `templates` for the workspace root, which globs never match
+pub const ROOT_KEY: &str = ".";
+
+/// Where ejected templates are written, relative to the workspace root
+const EJECT_DIR: &str = "templates";
+
+/// A built-in template's content, by file name
+pub fn builtin(name: &str) -> Option<&'static str> {
+    BUILTIN
+        .iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, content)| *content)
+}
+
+/// A built-in template's file name, from the name itself or without `Dockerfile.` and
+/// `.tera`, e.g. `cargo.bake`
+fn builtin_name(name: &str) -> Option<&'static str> {
+    BUILTIN.iter().map(|(file, _)| *file).find(|file| {
+        *file == name || file.strip_prefix("Dockerfile.").and_then(|file| file.strip_suffix(".tera")) == Some(name)
+    })
+}
+
+/// Explain which template a package's Dockerfile is rendered from. The package is given by
+/// target name, package name or path relative to the workspace root
+pub fn which(workspace_root: &Path, config: &BakehouseConfig, package: &str) -> Result<()> {
+    let workspace = resolvers::load_workspace(workspace_root, config)?;
+    let relative = |path: &Path| path.strip_prefix(workspace_root).unwrap_or(path).to_path_buf();
+
+    println!();
+    if package == workspace.name || package == "." {
+        match config.templates.get(ROOT_KEY) {
+            Some(template) => println!("{} uses {}, from '.'", workspace.name, relative(template).display()),
+            None => println!("{} uses its resolver's built-in template, as `templates` has no '.'", workspace.name),
+        }
+        return Ok(());
+    }
+
+    let (name, path) = workspace
+        .packages
+        .iter()
+        .map(|(name, found)| (name, relative(&found.path)))
+        .find(|(name, path)| {
+            **name == package || **name == sanitized_name(package) || path == Path::new(package.trim_end_matches('/'))
+        })
+        .ok_or_else(|| anyhow!("No package {} in the workspace", package))?;
+
+    let matches = config.matching_templates(&path);
+    let Some(((pattern, template), rest)) = matches.split_first() else {
+        println!(
+            "{} at {} uses its resolver's built-in template, as no glob under `templates` matches it",
+            name,
+            path.display()
+        );
+        return Ok(());
+    };
+
+    let reason = match rest.first() {
+        Some((other, _)) if specificity(other) == specificity(pattern) => {
+            "the last of the equally specific matching globs"
+        }
+        _ => "the most specific matching glob",
+    };
+    println!(
+        "{} at {} uses {}, from '{}', {}",
+        name,
+        path.display(),
+        relative(template).display(),
+        pattern,
+        reason
+    );
+    if !rest.is_empty() {
+        println!("Matching globs, most specific first:");
+        for (pattern, template) in &matches {
+            println!("- {}: {}", pattern, relative(template).display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy built-in templates into the workspace and register them in `.bakehouse` for the
+/// packages using them. Without a name, every built-in template the workspace uses is ejected
+pub fn eject(workspace_root: &Path, config: &BakehouseConfig, name: Option<&str>) -> Result<()> {
+    let workspace = resolvers::load_workspace(workspace_root, config)?;
+    let relative = |path: &Path| path.strip_prefix(workspace_root).unwrap_or(path).to_string_lossy().into_owned();
+
+    // Where each built-in template is used, as `templates` keys
+    let mut uses: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    if let Some(builtin) = workspace.dockerfile_template.builtin {
+        uses.entry(builtin).or_default().push(ROOT_KEY.to_string());
+    }
+    for package in workspace.packages.values() {
+        if let Some(builtin) = package.dockerfile_template.builtin {
+            uses.entry(builtin).or_default().push(relative(&package.path));
+        }
+    }
+
+    let names = match name {
+        Some(name) => vec![builtin_name(name).with_context(|| format!("No built-in template {}", name))?],
+        None => uses.keys().copied().collect(),
+    };
+    if names.is_empty() {
+        bail!("Every package already uses a template from .bakehouse");
+    }
+    for name in &names {
+        let path = workspace_root.join(EJECT_DIR).join(name);
+        if path.exists() {
+            bail!("{} already exists", path.display());
+        }
+    }
+
+    println!();
+    std::fs::create_dir_all(workspace_root.join(EJECT_DIR))?;
+    let mut entries = Vec::new();
+    for name in names {
+        let path = Path::new(EJECT_DIR).join(name);
+        std::fs::write(workspace_root.join(&path), builtin(name).unwrap_or_default())?;
+        println!("Ejected {}", path.display());
+
+        let Some(keys) = uses.get_mut(name) else {
+            println!("  No package uses it yet, so it isn't registered in .bakehouse");
+            continue;
+        };
+        // A template used by every package applies to all of them, new ones included
+        let package_count = keys.iter().filter(|key| *key != ROOT_KEY).count();
+        if package_count > 1 && package_count == workspace.packages.len() {
+            keys.retain(|key| key == ROOT_KEY);
+            keys.push("**".to_string());
+        }
+        for key in keys.iter() {
+            entries.push((key.clone(), path.to_string_lossy().into_owned()));
+        }
+    }
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let config_path = workspace_root.join(".bakehouse");
+    let existing = match config_path.exists() {
+        true => std::fs::read_to_string(&config_path)?,
+        false => String::new(),
+    };
+    std::fs::write(&config_path, register(&existing, &entries)?)?;
+    println!("Registered them under `templates` in {}", config_path.display());
+
+    Ok(())
+}
+
+/// Add entries under `templates` in the text of a `.bakehouse` file, leaving the rest as it is
+fn register(config: &str, entries: &[(String, String)]) -> Result<String> {
+    let mut lines: Vec<String> = config.lines().map(String::from).collect();
+    let entry = |indent: &str, (key, path): &(String, String)| format!("{}{:?}: {}", indent, key, path);
+
+    match lines.iter().position(|line| line.starts_with("templates:")) {
+        Some(index) => {
+            let rest = lines[index]["templates:".len()..].trim();
+            if rest == "{}" {
+                lines[index] = "templates:".to_string();
+            } else if !rest.is_empty() && !rest.starts_with('#') {
+                bail!("`templates` in .bakehouse isn't a block mapping, so add the ejected templates by hand");
+            }
+            // Keep to the indentation of the entries already there
+            let indent: String = lines[index + 1..]
+                .iter()
+                .find(|line| !line.trim().is_empty())
+                .filter(|line| line.starts_with(' '))
+                .map(|line| line.chars().take_while(|c| *c == ' ').collect())
+                .unwrap_or_else(|| "  ".to_string());
+            let added: Vec<_> = entries.iter().map(|added| entry(&indent, added)).collect();
+            lines.splice(index + 1..index + 1, added);
+        }
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push("templates:".to_string());
+            lines.extend(entries.iter().map(|added| entry("  ", added)));
+        }
+    }
+
+    let registered = lines.join("\n") + "\n";
+    serde_yaml::from_str::<BakehouseConfig>(&registered).context("Failed to register the templates in .bakehouse")?;
+    Ok(registered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builtin() {
+        assert!(builtin("Dockerfile.bake.tera").is_some_and(|content| content.starts_with("FROM {{ root }}")));
+        assert_eq!(builtin_name("cargo.bake"), Some("Dockerfile.cargo.bake.tera"));
+        assert_eq!(builtin_name("Dockerfile.root.tera"), Some("Dockerfile.root.tera"));
+        assert_eq!(builtin_name("jvm"), None);
+
+        // Every template in the source tree is compiled in
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/templates");
+        for file in fs::read_dir(dir).unwrap() {
+            let file = file.unwrap().file_name();
+            assert!(builtin(&file.to_string_lossy()).is_some(), "{:?} isn't built in", file);
+        }
+    }
+
+    #[test]
+    fn test_register() -> Result<()> {
+        let entries = [
+            ("**".to_string(), "templates/Dockerfile.bake.tera".to_string()),
+            (".".to_string(), "templates/Dockerfile.root.tera".to_string()),
+        ];
+        assert_eq!(
+            register("# Build settings\noutput_format: json\n", &entries)?,
+            "# Build settings\noutput_format: json\n\ntemplates:\n  \"**\": templates/Dockerfile.bake.tera\n  \".\": templates/Dockerfile.root.tera\n"
+        );
+        assert_eq!(
+            register("templates:\n    \"apps/*\": app.tera\nlint: true\n", &entries[..1])?,
+            "templates:\n    \"**\": templates/Dockerfile.bake.tera\n    \"apps/*\": app.tera\nlint: true\n"
+        );
+        assert_eq!(register("templates: {}\n", &entries[..1])?, "templates:\n  \"**\": templates/Dockerfile.bake.tera\n");
+        assert!(register("templates: { \"apps/*\": app.tera }\n", &entries).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eject() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::write(root.join("package.json"), r#"{"name": "mono", "version": "1.0.0"}"#)?;
+        fs::write(root.join("pnpm-workspace.yaml"), "packages:\n  - 'apps/*'\n")?;
+        for app in ["web", "api"] {
+            fs::create_dir_all(root.join("apps").join(app))?;
+            fs::write(
+                root.join("apps").join(app).join("package.json"),
+                format!(r#"{{"name": "@mono/{}", "version": "1.0.0"}}"#, app),
+            )?;
+        }
+
+        eject(root, &BakehouseConfig::default(), None)?;
+        assert_eq!(fs::read_to_string(root.join("templates/Dockerfile.bake.tera"))?, builtin("Dockerfile.bake.tera").unwrap());
+        assert!(root.join("templates/Dockerfile.root.tera").exists());
+
+        // The ejected templates are the ones used from now on
+        let config = BakehouseConfig::load(root)?;
+        assert_eq!(config.find_template(Path::new("apps/web")), Some(&root.join("templates/Dockerfile.bake.tera")));
+        assert_eq!(config.templates[ROOT_KEY], root.join("templates/Dockerfile.root.tera"));
+        let workspace = resolvers::load_workspace(root, &config)?;
+        assert!(workspace.dockerfile_template.builtin.is_none());
+        assert!(workspace.packages.values().all(|package| package.dockerfile_template.builtin.is_none()));
+
+        assert!(eject(root, &config, None).is_err());
+        assert!(eject(root, &config, Some("jvm")).is_err());
+
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tera::{self, Map};
 
+use crate::bake::sanitize_name;
+use crate::config::PackageSettings;
 use crate::dockerfile::DockerfileTemplate;
 
+/// File name used for generated Dockerfiles unless a resolver needs to avoid a clash
+pub const DEFAULT_DOCKERFILE: &str = "Dockerfile.bake";
+
 // Define traits for package information
 pub trait PackageInfo {
     fn name(&self) -> &str;
@@ -12,11 +19,80 @@ pub trait PackageInfo {
     fn dependencies(&self) -> &HashSet<String>;
     fn dockerfile_template(&self) -> &DockerfileTemplate;
 
+    /// Free-form labels used to group packages, e.g. Nx project tags
+    fn tags(&self) -> &[String] {
+        &[]
+    }
+
+    /// Whether the package produces an image that gets deployed, rather than only
+    /// feeding other builds
+    fn deployable(&self) -> bool {
+        false
+    }
+
+    /// Directories (relative to the package) holding what dependents need from it. When
+    /// empty, templates copy the whole package.
+    fn outputs(&self) -> &[String] {
+        &[]
+    }
+
+    /// Projects the package references for `tsc -b`, by package name, with the directory
+    /// (relative to the referenced package) its build output goes to, if any
+    fn references(&self) -> &[(String, Option<String>)] {
+        &[]
+    }
+
+    /// Build settings the package declares itself, e.g. in package.json
+    fn settings(&self) -> Option<&PackageSettings> {
+        None
+    }
+
+    /// Descriptive metadata, e.g. from package.json, that images are labelled with
+    fn metadata(&self) -> Option<&PackageMetadata> {
+        None
+    }
+
+    /// How the package uses each of its dependencies, by package name, where the resolver
+    /// knows. Dependencies left out are runtime ones
+    fn dependency_kinds(&self) -> Option<&HashMap<String, DependencyKind>> {
+        None
+    }
+
     fn sanitized_name(&self) -> String {
         sanitized_name(self.name())
     }
 }
 
+/// How a package uses a dependency, given to templates as each dependency's `kind`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    #[default]
+    Runtime,
+    Dev,
+    Build,
+}
+
+impl DependencyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencyKind::Runtime => "runtime",
+            DependencyKind::Dev => "dev",
+            DependencyKind::Build => "build",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PackageMetadata {
+    pub description: Option<String>,
+    pub license: Option<String>,
+    /// Browsable URL of the source repository
+    pub repository: Option<String>,
+    pub homepage: Option<String>,
+    pub author: Option<String>,
+}
+
 pub trait WorkspaceInfo {
     fn root_package(&self) -> &dyn PackageInfo;
     fn packages(&self) -> Vec<&dyn PackageInfo>;
@@ -28,19 +104,35 @@ pub struct Package {
     pub path: PathBuf,
     pub version: String,
     pub dependencies: HashMap<String, PathBuf>,
+    /// Kinds of the dependencies that aren't runtime ones, by target name
+    pub dependency_kinds: HashMap<String, DependencyKind>,
     pub dockerfile_template: DockerfileTemplate,
+    /// Dockerfile name, relative to `path`
+    pub dockerfile: String,
+    /// Name of the root target this package builds from
+    pub root: String,
+    pub tags: Vec<String>,
+    pub deployable: bool,
+    pub outputs: Vec<String>,
+    /// TypeScript project references, by target name, with their output directories
+    pub references: Vec<(String, Option<String>)>,
+    pub settings: PackageSettings,
+    pub metadata: PackageMetadata,
 }
 
 pub struct Workspace {
     pub name: String,
     pub path: PathBuf,
     pub version: String,
+    pub dockerfile: String,
     pub dockerfile_template: DockerfileTemplate,
     pub packages: HashMap<String, Package>,
 }
 
-fn sanitized_name(name: &str) -> String {
-    name.replace('@', "").replace('/', "-").to_lowercase()
+/// A package name as a bake target name: `@acme/ui` becomes `acme-ui` and
+/// `gopkg.in/yaml.v3` becomes `gopkg-in-yaml-v3`
+pub fn sanitized_name(name: &str) -> String {
+    sanitize_name(&name.replace('@', "")).to_lowercase()
 }
 
 impl Workspace {
@@ -51,6 +143,7 @@ impl Workspace {
             name: root.sanitized_name(),
             path: root.path().clone(),
             version: root.version().to_string(),
+            dockerfile: DEFAULT_DOCKERFILE.to_string(),
             dockerfile_template: root.dockerfile_template().clone(),
             packages: HashMap::new(),
         };
@@ -71,62 +164,244 @@ impl Workspace {
                 }
             }
 
-            let mut dockerfile_template = package_info.dockerfile_template().clone();
-
-            dockerfile_template.context.insert("path", &package_info.path().strip_prefix(&workspace.path)
-            .unwrap_or(package_info.path())
-            .to_string_lossy()
-            .to_string());
-            
-            // Convert dependencies to a format that Tera can iterate over directly
-            let deps_vec: Vec<_> = deps.iter().map(|(name, path)| {
-                let relative_path = path.strip_prefix(&workspace.path)
-                    .unwrap_or(path)
-                    .to_string_lossy()
-                    .to_string();
-                
-                tera::Value::Object({
-                    let mut m = Map::new();
-                    m.insert("name".to_string(), tera::Value::String(name.clone()));
-                    m.insert("path".to_string(), tera::Value::String(relative_path));
-                    m
-                })
-            }).collect();
-            
-            dockerfile_template.context.insert("dependencies", &deps_vec);
-
             // TODO - I don't think the workspace should capture this dependency here, we should do it as part of
             // of the Tera file generation
             deps.insert(root.sanitized_name(), root.path().clone());
 
-            workspace.add_package(
-                package_info.sanitized_name(),
-                package_info.path().clone(),
-                package_info.version().to_string(),
-                deps,
-                dockerfile_template
-            )
+            // A package living in the root directory (e.g. a single-crate Cargo project) can't share
+            // the root's Dockerfile name
+            let dockerfile = if package_info.path() == root.path() {
+                format!("Dockerfile.{}.bake", package_info.sanitized_name())
+            } else {
+                DEFAULT_DOCKERFILE.to_string()
+            };
+
+            workspace.add_package(Package {
+                name: package_info.sanitized_name(),
+                path: package_info.path().clone(),
+                version: package_info.version().to_string(),
+                dependency_kinds: package_info
+                    .dependency_kinds()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, kind)| (sanitized_name(name), *kind))
+                    .filter(|(name, kind)| *kind != DependencyKind::Runtime && deps.contains_key(name))
+                    .collect(),
+                dependencies: deps,
+                dockerfile_template: package_info.dockerfile_template().clone(),
+                dockerfile,
+                root: root.sanitized_name(),
+                tags: package_info.tags().to_vec(),
+                deployable: package_info.deployable(),
+                outputs: package_info.outputs().to_vec(),
+                references: package_info
+                    .references()
+                    .iter()
+                    .map(|(name, out_dir)| (sanitized_name(name), out_dir.clone()))
+                    .collect(),
+                settings: package_info.settings().cloned().unwrap_or_default(),
+                metadata: package_info.metadata().cloned().unwrap_or_default(),
+            });
         }
 
+        workspace.update_template_contexts();
+
         workspace
     }
 
-    fn add_package(
-        &mut self,
-        name: String,
-        path: PathBuf,
-        version: String,
-        dependencies: HashMap<String, PathBuf>,
-        dockerfile_template: DockerfileTemplate,
-    ) {
-        let package = Package {
-            name: name.clone(),
-            path,
-            version,
-            dependencies,
-            dockerfile_template,
+    /// Combine the workspaces found by several resolvers into one.
+    ///
+    /// The first workspace's root stays the root of the result; every other root becomes an
+    /// ordinary package. When more than one workspace is merged every target is prefixed so
+    /// that e.g. a pnpm `core` and a Cargo `core` don't collide.
+    pub fn merge(parts: Vec<(String, Workspace)>) -> Result<Self> {
+        let mut parts = parts.into_iter();
+        let (primary_prefix, mut merged) = parts
+            .next()
+            .ok_or_else(|| anyhow!("No workspaces to merge"))?;
+
+        let rest: Vec<_> = parts.collect();
+        if rest.is_empty() {
+            return Ok(merged);
+        }
+
+        merged.apply_prefix(&primary_prefix);
+
+        for (prefix, mut workspace) in rest {
+            workspace.apply_prefix(&prefix);
+
+            let root = Package {
+                name: workspace.name.clone(),
+                path: workspace.path.clone(),
+                version: workspace.version.clone(),
+                dependencies: HashMap::new(),
+                dependency_kinds: HashMap::new(),
+                dockerfile_template: workspace.dockerfile_template,
+                dockerfile: workspace.dockerfile,
+                root: workspace.name.clone(),
+                tags: vec![],
+                deployable: false,
+                outputs: vec![],
+                references: vec![],
+                settings: PackageSettings::default(),
+                metadata: PackageMetadata::default(),
+            };
+
+            for package in workspace.packages.into_values().chain(std::iter::once(root)) {
+                if merged.packages.contains_key(&package.name) || package.name == merged.name {
+                    return Err(anyhow!(
+                        "Package '{}' is defined by more than one resolver",
+                        package.name
+                    ));
+                }
+                merged.add_package(package);
+            }
+        }
+
+        merged.update_template_contexts();
+
+        Ok(merged)
+    }
+
+    /// Declare an extra dependency between two targets, e.g. a JS app that needs the WASM
+    /// output of a Rust crate
+    pub fn add_edge(&mut self, from: &str, to: &str) -> Result<()> {
+        let to_path = if to == self.name {
+            self.path.clone()
+        } else {
+            self.packages
+                .get(to)
+                .map(|p| p.path.clone())
+                .ok_or_else(|| anyhow!("Unknown edge target '{}' (dependency of '{}')", to, from))?
         };
-        self.packages.insert(name, package);
+
+        let package = self
+            .packages
+            .get_mut(from)
+            .ok_or_else(|| anyhow!("Unknown edge source '{}'", from))?;
+        package.dependencies.insert(to.to_string(), to_path);
+
+        self.update_template_contexts();
+
+        Ok(())
+    }
+
+    /// Prefix the root and every package name, keeping dependency references in step
+    fn apply_prefix(&mut self, prefix: &str) {
+        let rename = |name: &str| format!("{}-{}", prefix, name);
+
+        self.name = rename(&self.name);
+        self.dockerfile = format!("Dockerfile.{}.bake", prefix);
+        self.packages = self
+            .packages
+            .drain()
+            .map(|(name, mut package)| {
+                package.name = rename(&package.name);
+                package.root = rename(&package.root);
+                package.dependencies = package
+                    .dependencies
+                    .into_iter()
+                    .map(|(dep, path)| (rename(&dep), path))
+                    .collect();
+                package.dependency_kinds = package
+                    .dependency_kinds
+                    .into_iter()
+                    .map(|(dep, kind)| (rename(&dep), kind))
+                    .collect();
+                package.references = package
+                    .references
+                    .into_iter()
+                    .map(|(reference, out_dir)| (rename(&reference), out_dir))
+                    .collect();
+                (rename(&name), package)
+            })
+            .collect();
+    }
+
+    /// Refresh the values every package template can rely on: `root`, `path`, `dependencies`
+    /// and `references`.
+    /// Paths are relative to the root each package builds from, as that's where they live in the image.
+    fn update_template_contexts(&mut self) {
+        let root_paths: HashMap<&str, &Path> = std::iter::once((self.name.as_str(), self.path.as_path()))
+            .chain(self.packages.values().map(|p| (p.name.as_str(), p.path.as_path())))
+            .collect();
+
+        let outputs: HashMap<String, Vec<String>> = self
+            .packages
+            .values()
+            .map(|package| (package.name.clone(), package.outputs.clone()))
+            .collect();
+
+        let relative_paths: HashMap<String, String> = self
+            .packages
+            .values()
+            .map(|package| {
+                let root_path = root_paths.get(package.root.as_str()).copied().unwrap_or(&self.path);
+                let relative = package
+                    .path
+                    .strip_prefix(root_path)
+                    .unwrap_or(&package.path)
+                    .to_string_lossy()
+                    .to_string();
+                (package.name.clone(), relative)
+            })
+            .collect();
+
+        for package in self.packages.values_mut() {
+            let context = &mut package.dockerfile_template.context;
+            context.insert("root", &package.root);
+            context.insert("path", &relative_paths[&package.name]);
+            context.insert("outputs", &package.outputs);
+
+            // Convert dependencies to a format that Tera can iterate over directly
+            let mut deps_vec: Vec<_> = package
+                .dependencies
+                .keys()
+                .filter(|name| **name != package.root)
+                .map(|name| {
+                    let path = relative_paths.get(name).cloned().unwrap_or_default();
+                    tera::Value::Object({
+                        let mut m = Map::new();
+                        m.insert("name".to_string(), tera::Value::String(name.clone()));
+                        m.insert("path".to_string(), tera::Value::String(path));
+                        m.insert(
+                            "outputs".to_string(),
+                            outputs.get(name).cloned().unwrap_or_default().into(),
+                        );
+                        let kind = package.dependency_kinds.get(name).copied().unwrap_or_default();
+                        m.insert("kind".to_string(), kind.as_str().into());
+                        m
+                    })
+                })
+                .collect();
+            deps_vec.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+            context.insert("dependencies", &deps_vec);
+
+            // `tsc -b` needs each referenced project's tsconfig and, when it has one, its outDir
+            let references: Vec<_> = package
+                .references
+                .iter()
+                .filter(|(name, _)| package.dependencies.contains_key(name))
+                .map(|(name, out_dir)| {
+                    tera::Value::Object({
+                        let mut m = Map::new();
+                        m.insert("name".to_string(), tera::Value::String(name.clone()));
+                        m.insert(
+                            "path".to_string(),
+                            tera::Value::String(relative_paths.get(name).cloned().unwrap_or_default()),
+                        );
+                        m.insert("out_dir".to_string(), out_dir.clone().into());
+                        m
+                    })
+                })
+                .collect();
+            context.insert("references", &references);
+        }
+    }
+
+    fn add_package(&mut self, package: Package) {
+        self.packages.insert(package.name.clone(), package);
     }
 
     pub fn get_dependencies(&self, package_name: &str) -> Vec<(String, PathBuf)> {